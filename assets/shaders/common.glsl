@@ -0,0 +1,40 @@
+// shared between the shaders through #include "common.glsl"
+
+// chunk vertices are packed into two uints, see cubemesh::PackedVertex
+struct PackedVertex {
+    vec3 position;
+    vec2 uv;
+    float ao;
+    float scale;
+    float light;
+    uint layer;
+    vec3 normal;
+};
+
+// in the order of visibility::Face
+const vec3 FACE_NORMALS[6] = vec3[6](
+    vec3(1.0, 0.0, 0.0),
+    vec3(-1.0, 0.0, 0.0),
+    vec3(0.0, 1.0, 0.0),
+    vec3(0.0, -1.0, 0.0),
+    vec3(0.0, 0.0, 1.0),
+    vec3(0.0, 0.0, -1.0)
+);
+
+PackedVertex unpack_vertex(uvec2 data) {
+    PackedVertex v;
+    // positions and uvs are stored in sixteenths of a voxel
+    v.position = vec3(data.x & 1023u, (data.x >> 10) & 1023u, (data.x >> 20) & 1023u) / 16.0;
+    v.ao = float((data.x >> 30) & 3u);
+    v.uv = vec2(data.y & 31u, (data.y >> 5) & 31u) / 16.0;
+    v.normal = FACE_NORMALS[(data.y >> 10) & 7u];
+    v.scale = float(1u << ((data.y >> 13) & 3u));
+    v.light = float((data.y >> 15) & 15u);
+    v.layer = data.y >> 19;
+    return v;
+}
+
+// 1.0 for unoccluded corners down to 1.0 - AO_STRENGTH for fully occluded ones
+float ao_shade(float ao) {
+    return 1.0 - AO_STRENGTH * (3.0 - ao) / 3.0;
+}
\ No newline at end of file
@@ -0,0 +1,24 @@
+// distance fog blending towards the sky colour
+
+#include "sky.glsl"
+
+// 0 for linear, 1 for exponential, see sky::FogMode
+uniform int fog_mode;
+uniform float fog_start;
+uniform float fog_end;
+uniform float fog_density;
+
+// how much of a fragment `distance` blocks away is hidden by fog
+float fog_factor(float distance) {
+    if (fog_mode == 0) {
+        return clamp((distance - fog_start) / (fog_end - fog_start), 0.0, 1.0);
+    }
+    return 1.0 - exp(-fog_density * distance);
+}
+
+// `relative_position` is the fragment's position relative to the camera
+vec4 apply_fog(vec4 color, vec3 relative_position) {
+    float fog = fog_factor(length(relative_position));
+    // fogged translucent faces become opaque so they fade into the sky too
+    return vec4(mix(color.rgb, sky_color(relative_position), fog), mix(color.a, 1.0, fog));
+}
\ No newline at end of file
@@ -0,0 +1,43 @@
+#version 140
+
+#ifdef ENABLE_FOG
+#include "fog.glsl"
+#endif
+#ifdef ENABLE_SHADOWS
+#include "shadow.glsl"
+#endif
+
+in vec2 v_tex_coords;
+in float v_shade;
+flat in uint v_layer;
+in vec3 v_relative_position;
+flat in vec3 v_normal;
+in float v_view_depth;
+
+out vec4 color;
+
+uniform sampler2DArray tex;
+// texels below this alpha are discarded, used for cutout blocks like leaves
+uniform float alpha_cutoff;
+// multiplied into the alpha of translucent blocks
+uniform float opacity;
+// colour and brightness of the daylight, see daycycle.rs
+uniform vec3 sun_color;
+uniform float sky_light;
+uniform vec3 eye;
+
+void main() {
+    vec4 texel = texture(tex, vec3(v_tex_coords, float(v_layer)));
+    if (texel.a < alpha_cutoff) {
+        discard;
+    }
+    vec3 light = sun_color * sky_light;
+#ifdef ENABLE_SHADOWS
+    float shadow = shadow_visibility(eye + v_relative_position, v_normal, v_view_depth);
+    light *= mix(1.0 - SHADOW_STRENGTH, 1.0, shadow);
+#endif
+    color = vec4(texel.rgb * v_shade * light, texel.a * opacity);
+#ifdef ENABLE_FOG
+    color = apply_fog(color, v_relative_position);
+#endif
+}
\ No newline at end of file
@@ -0,0 +1,52 @@
+// cascaded shadow map lookups, see shadow.rs
+
+uniform sampler2DArrayShadow shadow_maps;
+// one matrix per cascade, from world space to its shadow map
+uniform mat4 light_matrix0;
+uniform mat4 light_matrix1;
+uniform mat4 light_matrix2;
+// view depth each cascade ends at
+uniform vec3 cascade_far;
+// size of a shadow map texel in blocks for each cascade
+uniform vec3 cascade_texel;
+// direction towards the sun, or the moon at night
+uniform vec3 light_direction;
+
+// how much of the light reaches a point, from 0 in shadow to 1 fully lit
+float shadow_visibility(vec3 world_position, vec3 normal, float view_depth) {
+    int cascade;
+    mat4 light_matrix;
+    if (view_depth < cascade_far.x) {
+        cascade = 0;
+        light_matrix = light_matrix0;
+    } else if (view_depth < cascade_far.y) {
+        cascade = 1;
+        light_matrix = light_matrix1;
+    } else if (view_depth < cascade_far.z) {
+        cascade = 2;
+        light_matrix = light_matrix2;
+    } else {
+        return 1.0;
+    }
+
+    // faces turned away from the light are shadowed by their own block
+    if (dot(normal, light_direction) <= 0.0) {
+        return 0.0;
+    }
+
+    // look up a little way off the surface so it doesn't shadow itself
+    vec3 offset = normal * cascade_texel[cascade] * 1.5;
+    vec4 position = light_matrix * vec4(world_position + offset, 1.0);
+    vec3 coords = position.xyz * 0.5 + 0.5;
+
+    // 3x3 percentage closer filtering
+    float texel = 1.0 / float(SHADOW_MAP_SIZE);
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            vec2 uv = coords.xy + vec2(x, y) * texel;
+            lit += texture(shadow_maps, vec4(uv, float(cascade), coords.z - 0.0005));
+        }
+    }
+    return lit / 9.0;
+}
\ No newline at end of file
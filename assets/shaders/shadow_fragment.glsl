@@ -0,0 +1,14 @@
+#version 150
+
+in vec2 v_tex_coords;
+flat in uint v_layer;
+
+uniform sampler2DArray tex;
+uniform float alpha_cutoff;
+
+// only depth is written, holes in cutout blocks let the light through
+void main() {
+    if (texture(tex, vec3(v_tex_coords, float(v_layer))).a < alpha_cutoff) {
+        discard;
+    }
+}
\ No newline at end of file
@@ -0,0 +1,19 @@
+#version 150
+
+#include "common.glsl"
+
+in uvec2 data;
+
+// world space to the shadow map of the cascade being drawn
+uniform mat4 light_matrix;
+uniform vec3 chunk_offset;
+
+out vec2 v_tex_coords;
+flat out uint v_layer;
+
+void main() {
+    PackedVertex v = unpack_vertex(data);
+    v_tex_coords = v.uv * v.scale;
+    v_layer = v.layer;
+    gl_Position = light_matrix * vec4(v.position + chunk_offset, 1.0);
+}
\ No newline at end of file
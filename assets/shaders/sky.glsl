@@ -0,0 +1,11 @@
+// sky gradient, shared by the sky and the fog through #include "sky.glsl"
+
+uniform vec3 sky_horizon;
+uniform vec3 sky_zenith;
+
+// colour of the sky in a world space direction, below the horizon is
+// the horizon colour
+vec3 sky_color(vec3 direction) {
+    float height = max(normalize(direction).y, 0.0);
+    return mix(sky_horizon, sky_zenith, sqrt(height));
+}
\ No newline at end of file
@@ -0,0 +1,22 @@
+#version 150
+
+#include "sky.glsl"
+
+in vec3 v_direction;
+
+// direction towards the sun and the colour of its light, see daycycle.rs
+uniform vec3 sun_direction;
+uniform vec3 sun_color;
+
+out vec4 color;
+
+void main() {
+    vec3 direction = normalize(v_direction);
+    vec3 sky = sky_color(direction);
+    // the sun disc with a soft edge, the moon is opposite it
+    float sun = smoothstep(0.9990, 0.9995, dot(direction, sun_direction));
+    float moon = smoothstep(0.9994, 0.9997, dot(direction, -sun_direction));
+    sky = mix(sky, sun_color * 1.5, sun);
+    sky = mix(sky, vec3(0.85, 0.85, 0.9), moon);
+    color = vec4(sky, 1.0);
+}
\ No newline at end of file
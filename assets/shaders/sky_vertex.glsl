@@ -0,0 +1,16 @@
+#version 150
+
+in vec2 position;
+
+uniform mat4 perspective;
+uniform mat4 view;
+
+out vec3 v_direction;
+
+void main() {
+    // world space direction through this corner of the screen, ignoring
+    // the camera's position
+    vec4 eye_direction = inverse(perspective) * vec4(position, 1.0, 1.0);
+    v_direction = transpose(mat3(view)) * (eye_direction.xyz / eye_direction.w);
+    gl_Position = vec4(position, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+#version 150
+
+in vec3 v_direction;
+
+uniform samplerCube skybox;
+// daylight colour and brightness, so the skybox darkens at night
+uniform vec3 tint;
+
+out vec4 color;
+
+void main() {
+    color = vec4(texture(skybox, v_direction).rgb * tint, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,18 @@
+#version 150
+
+in vec3 position;
+
+uniform mat4 perspective;
+// the camera's view matrix without its translation
+uniform mat4 view;
+// depth of the far plane, 1 or 0 with reverse-z
+uniform float far_depth;
+
+out vec3 v_direction;
+
+void main() {
+    v_direction = position;
+    // scaling z with w puts the cube on the far plane, behind everything
+    vec4 clip = perspective * view * vec4(position, 1.0);
+    gl_Position = vec4(clip.xy, far_depth * clip.w, clip.w);
+}
\ No newline at end of file
@@ -0,0 +1,35 @@
+#version 150
+
+#include "common.glsl"
+
+// see cubemesh::PackedVertex for the layout
+in uvec2 data;
+
+uniform mat4 perspective;
+uniform mat4 view;
+uniform vec3 chunk_offset;
+// camera position, for fog and shadows
+uniform vec3 eye;
+
+out vec2 v_tex_coords;
+out float v_shade;
+flat out uint v_layer;
+out vec3 v_relative_position;
+flat out vec3 v_normal;
+// distance in front of the camera, to pick a shadow cascade
+out float v_view_depth;
+
+void main() {
+    PackedVertex v = unpack_vertex(data);
+    v_layer = v.layer;
+
+    // repeat the texture across merged LOD voxels instead of stretching it
+    v_tex_coords = v.uv * v.scale;
+    v_shade = ao_shade(v.ao) * (v.light / 15.0);
+    vec3 world_position = v.position + chunk_offset;
+    v_relative_position = world_position - eye;
+    v_normal = v.normal;
+    vec4 view_position = view * vec4(world_position, 1.0);
+    v_view_depth = view_position.z;
+    gl_Position = perspective * view_position;
+}
\ No newline at end of file
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+// copies of the shipped assets baked into the binary, used whenever a file
+// can't be found in the asset directory
+macro_rules! embed {
+    ($($path:literal),* $(,)?) => {
+        &[$(($path, include_bytes!(concat!("../assets/", $path)))),*]
+    };
+}
+
+const EMBEDDED: &[(&str, &[u8])] = embed![
+    "bindings.ron",
+    "blocks/dirt.ron",
+    "blocks/flower.ron",
+    "blocks/glass.ron",
+    "blocks/grass.ron",
+    "blocks/lava.ron",
+    "blocks/leaves.ron",
+    "blocks/plank_slab.ron",
+    "blocks/plank_stairs.ron",
+    "blocks/planks.ron",
+    "blocks/sand.ron",
+    "blocks/stone.ron",
+    "blocks/water.ron",
+    "models/models.ron",
+    "shaders/common.glsl",
+    "shaders/fog.glsl",
+    "shaders/fragment.glsl",
+    "shaders/shadow.glsl",
+    "shaders/shadow_fragment.glsl",
+    "shaders/shadow_vertex.glsl",
+    "shaders/sky.glsl",
+    "shaders/sky_fragment.glsl",
+    "shaders/sky_vertex.glsl",
+    "shaders/skybox_fragment.glsl",
+    "shaders/skybox_vertex.glsl",
+    "shaders/vertex.glsl",
+    "textures/dirt.png",
+    "textures/flower.png",
+    "textures/glass.png",
+    "textures/grass.png",
+    "textures/lava.png",
+    "textures/leaves.png",
+    "textures/planks.png",
+    "textures/sand.png",
+    "textures/stone.png",
+    "textures/water.png",
+];
+
+#[derive(Debug)]
+pub enum AssetError {
+    NotFound { path: String },
+    Io { path: PathBuf, error: std::io::Error },
+    Utf8 { path: String },
+    Image { path: String, error: image::ImageError },
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::NotFound { path } => write!(f, "asset {} does not exist", path),
+            AssetError::Io { path, error } => write!(f, "could not read {}: {}", path.display(), error),
+            AssetError::Utf8 { path } => write!(f, "asset {} is not valid UTF-8", path),
+            AssetError::Image { path, error } => write!(f, "could not decode image {}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+/// Resolves asset paths like `textures/grass.png` against an asset directory
+/// on disk, falling back to the copies embedded in the binary, and caches
+/// every file it reads.
+pub struct AssetManager {
+    root: PathBuf,
+    cache: HashMap<String, Rc<[u8]>>,
+}
+
+impl AssetManager {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn embedded(path: &str) -> Option<&'static [u8]> {
+        EMBEDDED.iter().find(|(p, _)| *p == path).map(|(_, bytes)| *bytes)
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.cache.contains_key(path) || self.root.join(path).is_file() || Self::embedded(path).is_some()
+    }
+
+    // last modification time of the file on disk, `None` for embedded assets
+    pub fn modified(&self, path: &str) -> Option<SystemTime> {
+        fs::metadata(self.root.join(path)).and_then(|m| m.modified()).ok()
+    }
+
+    // drop a cached file so the next load reads it again
+    pub fn invalidate(&mut self, path: &str) {
+        self.cache.remove(path);
+    }
+
+    pub fn load_bytes(&mut self, path: &str) -> Result<Rc<[u8]>, AssetError> {
+        if let Some(bytes) = self.cache.get(path) {
+            return Ok(bytes.clone());
+        }
+
+        let file = self.root.join(path);
+        let bytes: Rc<[u8]> = if file.is_file() {
+            fs::read(&file).map_err(|error| AssetError::Io { path: file, error })?.into()
+        } else {
+            Self::embedded(path).ok_or_else(|| AssetError::NotFound { path: path.to_string() })?.into()
+        };
+        self.cache.insert(path.to_string(), bytes.clone());
+        Ok(bytes)
+    }
+
+    pub fn load_string(&mut self, path: &str) -> Result<String, AssetError> {
+        let bytes = self.load_bytes(path)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| AssetError::Utf8 { path: path.to_string() })
+    }
+
+    pub fn load_image(&mut self, path: &str) -> Result<image::RgbaImage, AssetError> {
+        let bytes = self.load_bytes(path)?;
+        let image = image::load_from_memory(&bytes)
+            .map_err(|error| AssetError::Image { path: path.to_string(), error })?;
+        Ok(image.to_rgba8())
+    }
+
+    /// Paths of the files directly inside `dir` with the given extension,
+    /// sorted. They come from the asset directory when it has `dir`, so files
+    /// removed or renamed there stay gone, and from the embedded assets only
+    /// when it doesn't.
+    pub fn list(&self, dir: &str, extension: &str) -> Result<Vec<String>, AssetError> {
+        let disk = self.root.join(dir);
+        let mut paths: Vec<String> = if disk.is_dir() {
+            let entries = fs::read_dir(&disk).map_err(|error| AssetError::Io { path: disk.clone(), error })?;
+            let mut paths = Vec::new();
+            for entry in entries {
+                let entry = entry.map_err(|error| AssetError::Io { path: disk.clone(), error })?;
+                if let Some(name) = entry.file_name().to_str() {
+                    paths.push(format!("{}/{}", dir, name));
+                }
+            }
+            paths
+        } else {
+            EMBEDDED
+                .iter()
+                .map(|(p, _)| p.to_string())
+                .filter(|p| Path::new(p).parent() == Some(Path::new(dir)))
+                .collect()
+        };
+
+        paths.retain(|p| Path::new(p).extension().is_some_and(|e| e == extension));
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every file under `dir`, relative to `root`
+    fn files(root: &Path, dir: &Path, found: &mut Vec<String>) {
+        for entry in fs::read_dir(root.join(dir)).unwrap() {
+            let path = dir.join(entry.unwrap().file_name());
+            if root.join(&path).is_dir() {
+                files(root, &path, found);
+            } else {
+                found.push(path.to_str().unwrap().replace('\\', "/"));
+            }
+        }
+    }
+
+    #[test]
+    fn embedded_matches_assets() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join(crate::ASSETS);
+        let mut on_disk = Vec::new();
+        files(&root, Path::new(""), &mut on_disk);
+        on_disk.sort();
+        let mut embedded: Vec<String> = EMBEDDED.iter().map(|(p, _)| p.to_string()).collect();
+        embedded.sort();
+        assert_eq!(embedded, on_disk, "EMBEDDED in assets.rs is out of date");
+    }
+
+    #[test]
+    fn list_prefers_disk() {
+        let root = std::env::temp_dir().join(format!("ferrous_assets_{}", std::process::id()));
+        fs::create_dir_all(root.join("blocks")).unwrap();
+        fs::write(root.join("blocks/only.ron"), "").unwrap();
+        fs::write(root.join("blocks/notes.txt"), "").unwrap();
+        let assets = AssetManager::new(&root);
+        assert_eq!(assets.list("blocks", "ron").unwrap(), ["blocks/only.ron"]);
+        // directories missing on disk still come from the binary
+        assert_eq!(assets.list("models", "ron").unwrap(), ["models/models.ron"]);
+        fs::remove_dir_all(&root).unwrap();
+
+        let embedded = AssetManager::new(root.join("missing"));
+        assert_eq!(embedded.list("blocks", "ron").unwrap().len(), 12);
+    }
+}
@@ -0,0 +1,35 @@
+/// Frame times collected while a timeline plays, summed up at the end.
+pub struct FrameTimes {
+    // in seconds
+    times: Vec<f32>,
+}
+
+impl FrameTimes {
+    pub fn new() -> Self {
+        Self { times: Vec::new() }
+    }
+
+    pub fn push(&mut self, dt: f32) {
+        self.times.push(dt);
+    }
+
+    /// Frame count, average frame time and frame rate, and the average of the
+    /// slowest 1% of frames.
+    pub fn summary(&self) -> String {
+        if self.times.is_empty() {
+            return "no frames".to_string();
+        }
+        let mut sorted = self.times.clone();
+        sorted.sort_by(|a, b| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+        let average = sorted.iter().sum::<f32>() / sorted.len() as f32;
+        let slowest = &sorted[..sorted.len().div_ceil(100)];
+        let low = slowest.iter().sum::<f32>() / slowest.len() as f32;
+        format!(
+            "{} frames, {:.2} ms average ({:.1} fps), {:.2} ms 1% low",
+            sorted.len(),
+            average * 1000.0,
+            1.0 / average,
+            low * 1000.0,
+        )
+    }
+}
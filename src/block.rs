@@ -0,0 +1,560 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::assets::{AssetError, AssetManager};
+use crate::model::{BakedModel, ModelRegistry};
+use crate::visibility::Face;
+
+// block id stored in the voxel maps for empty space, every other id is
+// assigned when the block definitions are loaded
+pub const AIR: i8 = 0;
+
+/// Which pass a block's faces are drawn in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+pub enum RenderLayer {
+    // fully opaque, hides whatever is behind it
+    #[default]
+    Opaque,
+    // drawn in the opaque pass with alpha testing, holes can be seen through
+    Cutout,
+    // drawn after the opaque pass with blending, sorted back to front
+    Translucent,
+}
+
+/// Texture names for a block's faces, the most specific one wins:
+/// a named face, then `side` for the four horizontal faces, then `all`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct BlockTextures {
+    pub all: Option<String>,
+    pub side: Option<String>,
+    pub top: Option<String>,
+    pub bottom: Option<String>,
+    pub north: Option<String>,
+    pub south: Option<String>,
+    pub east: Option<String>,
+    pub west: Option<String>,
+}
+
+impl BlockTextures {
+    fn for_face(&self, face: Face) -> Option<&String> {
+        let specific = match face {
+            Face::Top => &self.top,
+            Face::Bottom => &self.bottom,
+            Face::North => &self.north,
+            Face::South => &self.south,
+            Face::East => &self.east,
+            Face::West => &self.west,
+        };
+        let side = match face {
+            Face::Top | Face::Bottom => None,
+            _ => self.side.as_ref(),
+        };
+        specific.as_ref().or(side).or(self.all.as_ref())
+    }
+}
+
+/// How a fluid block flows. Each fluid is registered as a source block
+/// followed by one block for each flow level from 1 to `MAX_FLUID_LEVEL`.
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct FluidDefinition {
+    // how many blocks it flows sideways from a source, from 1 to 7
+    pub flow_distance: u8,
+    // game ticks between updates, lava flows slower than water
+    pub tick_interval: u32,
+    // whether a gap between two sources fills in as a new source
+    #[serde(default)]
+    pub renewable: bool,
+}
+
+impl FluidDefinition {
+    // how much the level goes up with each block flowed, so every fluid
+    // thins out to the lowest level by the end of its flow distance
+    pub fn level_step(&self) -> u8 {
+        (MAX_FLUID_LEVEL / self.flow_distance).max(1)
+    }
+}
+
+// flowing fluid levels go from 1 to this, 0 is a source
+pub const MAX_FLUID_LEVEL: u8 = 7;
+
+// brightest light a block can give off
+pub const MAX_LIGHT_EMISSION: u8 = 15;
+
+/// The fluid a block is part of.
+#[derive(Clone, Copy, Debug)]
+pub struct Fluid {
+    // id of the fluid's source block, which also identifies the fluid
+    pub source: i8,
+    pub level: u8,
+    pub definition: FluidDefinition,
+}
+
+/// How a block changes over time, as written in a block definition file.
+#[derive(Deserialize, Debug, Clone)]
+pub enum TickBehavior {
+    // on random ticks turns a nearby `onto` block into this one, and goes
+    // back to `onto` itself once something covers it, like grass on dirt
+    Spread { onto: String },
+    // turns into `into` on a random tick, like a crop growing a stage
+    Grow { into: String },
+    // falls whenever there is air below it, like sand
+    Fall,
+}
+
+/// A `TickBehavior` with its block names resolved to ids.
+#[derive(Clone, Copy, Debug)]
+pub enum BlockTick {
+    Spread { onto: i8 },
+    Grow { into: i8 },
+    Fall,
+}
+
+// game ticks between a falling block moving down each block
+pub const FALL_DELAY: u32 = 2;
+
+/// A block as written in a block definition file.
+#[derive(Deserialize, Debug)]
+pub struct BlockDefinition {
+    pub name: String,
+    pub textures: BlockTextures,
+    // name of a model in `models/models.ron`, plain cubes have none
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub render_layer: RenderLayer,
+    pub solid: bool,
+    #[serde(default)]
+    pub light_emission: u8,
+    pub hardness: f32,
+    #[serde(default)]
+    pub fluid: Option<FluidDefinition>,
+    #[serde(default)]
+    pub tick: Option<TickBehavior>,
+}
+
+/// A loaded block, with its textures resolved to texture array layers.
+pub struct Block {
+    pub name: String,
+    // texture array layer for each face, indexed by `Face`
+    pub texture_layers: [u16; 6],
+    pub model: Option<String>,
+    pub render_layer: RenderLayer,
+    pub solid: bool,
+    pub fluid: Option<Fluid>,
+    pub tick: Option<BlockTick>,
+}
+
+#[derive(Debug)]
+pub enum BlockError {
+    Asset(AssetError),
+    Parse { path: String, error: ron::error::SpannedError },
+    DuplicateName { name: String, path: String },
+    MissingFaceTexture { name: String, face: Face },
+    MissingTexture { name: String, texture: String, path: String },
+    UnknownModel { name: String, model: String },
+    InvalidFluid { name: String },
+    InvalidProperties { name: String },
+    UnknownBlock { name: String, block: String },
+    TooManyBlocks,
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockError::Asset(error) => error.fmt(f),
+            BlockError::Parse { path, error } => write!(f, "could not parse {}: {}", path, error),
+            BlockError::DuplicateName { name, path } => {
+                write!(f, "block \"{}\" in {} is already defined", name, path)
+            },
+            BlockError::MissingFaceTexture { name, face } => {
+                write!(f, "block \"{}\" has no texture for its {:?} face", name, face)
+            },
+            BlockError::MissingTexture { name, texture, path } => {
+                write!(f, "block \"{}\" uses texture \"{}\" but {} does not exist", name, texture, path)
+            },
+            BlockError::UnknownModel { name, model } => {
+                write!(f, "block \"{}\" uses unknown model \"{}\"", name, model)
+            },
+            BlockError::InvalidFluid { name } => {
+                write!(f, "fluid \"{}\" must flow between 1 and {} blocks and can't have a model", name, MAX_FLUID_LEVEL)
+            },
+            BlockError::InvalidProperties { name } => {
+                write!(f, "block \"{}\" must have a light emission from 0 to {} and a hardness of at least 0", name, MAX_LIGHT_EMISSION)
+            },
+            BlockError::UnknownBlock { name, block } => {
+                write!(f, "block \"{}\" ticks into unknown block \"{}\"", name, block)
+            },
+            BlockError::TooManyBlocks => write!(f, "there are more than {} blocks defined", i8::MAX),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+impl From<AssetError> for BlockError {
+    fn from(error: AssetError) -> Self {
+        BlockError::Asset(error)
+    }
+}
+
+/// Every block the mesher and world know about, indexed by the id stored in
+/// the voxel maps.
+pub struct BlockRegistry {
+    // index 0 is air, which has no definition
+    blocks: Vec<Option<Block>>,
+    ids: HashMap<String, i8>,
+    // every name from `names`, including air and flowing fluid levels
+    saved_ids: HashMap<String, i8>,
+    // texture names in texture array layer order
+    textures: Vec<String>,
+    models: ModelRegistry,
+}
+
+impl BlockRegistry {
+    /// Reads every `.ron` file in `blocks/`, in file name order so block ids
+    /// are stable between runs, and checks that the textures they use exist.
+    pub fn load(assets: &mut AssetManager, models: ModelRegistry) -> Result<Self, BlockError> {
+        let mut registry = Self {
+            blocks: vec![None],
+            ids: HashMap::new(),
+            saved_ids: HashMap::from([("air".to_string(), AIR)]),
+            textures: Vec::new(),
+            models,
+        };
+        // tick behaviours can name blocks defined in later files, so they're
+        // resolved once everything is registered
+        let mut ticks = Vec::new();
+        for path in assets.list("blocks", "ron")? {
+            let source = assets.load_string(&path)?;
+            let mut definition: BlockDefinition = ron::from_str(&source)
+                .map_err(|error| BlockError::Parse { path: path.clone(), error })?;
+            if let Some(tick) = definition.tick.take() {
+                ticks.push((definition.name.clone(), tick));
+            }
+            registry.register(assets, &path, definition)?;
+        }
+        for (name, tick) in ticks {
+            let resolve = |block: &String| {
+                registry.id(block).ok_or_else(|| BlockError::UnknownBlock { name: name.clone(), block: block.clone() })
+            };
+            let tick = match &tick {
+                TickBehavior::Spread { onto } => BlockTick::Spread { onto: resolve(onto)? },
+                TickBehavior::Grow { into } => BlockTick::Grow { into: resolve(into)? },
+                TickBehavior::Fall => BlockTick::Fall,
+            };
+            let id = registry.id(&name).unwrap();
+            registry.blocks[id as usize].as_mut().unwrap().tick = Some(tick);
+        }
+        Ok(registry)
+    }
+
+    fn register(&mut self, assets: &AssetManager, path: &str, definition: BlockDefinition) -> Result<(), BlockError> {
+        let name = definition.name;
+        if name == "air" || self.ids.contains_key(&name) {
+            return Err(BlockError::DuplicateName { name, path: path.to_string() });
+        }
+        if let Some(model) = &definition.model {
+            if self.models.get(model).is_none() {
+                return Err(BlockError::UnknownModel { name, model: model.clone() });
+            }
+        }
+        if let Some(fluid) = &definition.fluid {
+            if fluid.flow_distance == 0 || fluid.flow_distance > MAX_FLUID_LEVEL || definition.model.is_some() {
+                return Err(BlockError::InvalidFluid { name });
+            }
+        }
+        // not used by the renderer yet, but checked so definitions stay valid
+        // once there is lighting and block breaking takes time
+        if definition.light_emission > MAX_LIGHT_EMISSION || definition.hardness.is_nan() || definition.hardness < 0.0 {
+            return Err(BlockError::InvalidProperties { name });
+        }
+
+        let mut texture_layers = [0; 6];
+        for face in Face::ALL {
+            let texture = definition.textures.for_face(face)
+                .ok_or_else(|| BlockError::MissingFaceTexture { name: name.clone(), face })?;
+            texture_layers[face as usize] = self.add_texture(assets, &name, texture)?;
+        }
+
+        let id = i8::try_from(self.blocks.len()).map_err(|_| BlockError::TooManyBlocks)?;
+        self.ids.insert(name.clone(), id);
+        let block = |name: String, fluid: Option<Fluid>| Block {
+            name,
+            texture_layers,
+            model: definition.model.clone(),
+            render_layer: definition.render_layer,
+            solid: definition.solid,
+            fluid,
+            tick: None,
+        };
+
+        match definition.fluid {
+            None => {
+                self.saved_ids.insert(name.clone(), id);
+                self.blocks.push(Some(block(name, None)));
+            },
+            Some(fluid) => {
+                // flowing levels get the ids right after the source so
+                // `fluid_block` can find them
+                for level in 0..=MAX_FLUID_LEVEL {
+                    if i8::try_from(self.blocks.len()).is_err() {
+                        return Err(BlockError::TooManyBlocks);
+                    }
+                    let name = if level == 0 { name.clone() } else { format!("{}:{}", name, level) };
+                    self.saved_ids.insert(name.clone(), id + level as i8);
+                    self.blocks.push(Some(block(name, Some(Fluid { source: id, level, definition: fluid }))));
+                }
+            },
+        }
+        Ok(())
+    }
+
+    // layer of a texture in the texture array, adding it if this is its first use
+    fn add_texture(&mut self, assets: &AssetManager, name: &str, texture: &str) -> Result<u16, BlockError> {
+        if let Some(layer) = self.textures.iter().position(|t| t == texture) {
+            return Ok(layer as u16);
+        }
+        let path = texture_path(texture);
+        if !assets.exists(&path) {
+            return Err(BlockError::MissingTexture { name: name.to_string(), texture: texture.to_string(), path });
+        }
+        self.textures.push(texture.to_string());
+        Ok(self.textures.len() as u16 - 1)
+    }
+
+    pub fn get(&self, block: i8) -> Option<&Block> {
+        self.blocks.get(block as usize).and_then(|b| b.as_ref())
+    }
+
+    // id of a block by name, for world generation
+    pub fn id(&self, name: &str) -> Option<i8> {
+        self.ids.get(name).copied()
+    }
+
+    // every block's name in id order starting with air, flowing fluid levels
+    // being named like "water:3". saved worlds store these so they still
+    // load when block ids change
+    pub fn names(&self) -> Vec<&str> {
+        self.blocks.iter().map(|b| b.as_ref().map_or("air", |b| b.name.as_str())).collect()
+    }
+
+    // id of a block by one of the names from `names`
+    pub fn lookup(&self, name: &str) -> Option<i8> {
+        self.saved_ids.get(name).copied()
+    }
+
+    pub fn texture_names(&self) -> &[String] {
+        &self.textures
+    }
+
+    pub fn texture_layer(&self, block: i8, face: Face) -> u16 {
+        self.get(block).map_or(0, |b| b.texture_layers[face as usize])
+    }
+
+    // `None` for blocks that aren't drawn at all
+    pub fn render_layer(&self, block: i8) -> Option<RenderLayer> {
+        self.get(block).map(|b| b.render_layer)
+    }
+
+    pub fn model(&self, block: i8) -> Option<&BakedModel> {
+        self.get(block)
+            .and_then(|b| b.model.as_ref())
+            .and_then(|name| self.models.get(name))
+    }
+
+    pub fn fluid(&self, block: i8) -> Option<&Fluid> {
+        self.get(block).and_then(|b| b.fluid.as_ref())
+    }
+
+    // blocks that can be stood on and walked into, air is not solid
+    pub fn is_solid(&self, block: i8) -> bool {
+        self.get(block).is_some_and(|b| b.solid)
+    }
+
+    pub fn falls(&self, block: i8) -> bool {
+        matches!(self.get(block).and_then(|b| b.tick), Some(BlockTick::Fall))
+    }
+
+    // game ticks until the block next to a change should be updated, `None`
+    // for blocks that don't react to their neighbours
+    pub fn tick_delay(&self, block: i8) -> Option<u32> {
+        if self.falls(block) {
+            return Some(FALL_DELAY);
+        }
+        self.fluid(block).map(|f| f.definition.tick_interval)
+    }
+
+    // the block for a fluid at a flow level, `source` being the fluid's source block
+    pub fn fluid_block(&self, source: i8, level: u8) -> i8 {
+        source + level as i8
+    }
+
+    // height of a fluid's surface in model units, lower the further it has flowed
+    pub fn fluid_height(&self, block: i8) -> Option<i32> {
+        self.fluid(block).map(|f| 14 - 12 * f.level as i32 / MAX_FLUID_LEVEL as i32)
+    }
+
+    // opaque full cubes, the only blocks that can't be seen through
+    pub fn is_opaque(&self, block: i8) -> bool {
+        self.render_layer(block) == Some(RenderLayer::Opaque) && self.model(block).is_none() && self.fluid(block).is_none()
+    }
+
+    // whether the block's model completely covers one of its faces
+    pub fn is_full_face(&self, block: i8, face: Face) -> bool {
+        if self.fluid(block).is_some() {
+            // fluids don't reach the top of the block
+            return face == Face::Bottom;
+        }
+        match self.model(block) {
+            None => true,
+            Some(model) => model.is_full(face),
+        }
+    }
+
+    // whether `block` needs to draw its `face`, which is shared with `neighbour`
+    pub fn face_visible(&self, block: i8, neighbour: i8, face: Face) -> bool {
+        if let (Some(a), Some(b)) = (self.fluid(block), self.fluid(neighbour)) {
+            if a.source == b.source {
+                // only the part of a side sticking out above a lower neighbour shows
+                return match face {
+                    Face::Top | Face::Bottom => false,
+                    _ => self.fluid_height(neighbour) < self.fluid_height(block),
+                };
+            }
+        }
+        match self.render_layer(neighbour) {
+            None | Some(RenderLayer::Cutout) => true,
+            Some(RenderLayer::Opaque) => !self.is_full_face(neighbour, face.opposite()),
+            // the inside of a body of water or a wall of glass is not drawn
+            Some(RenderLayer::Translucent) => block != neighbour,
+        }
+    }
+}
+
+pub fn texture_path(texture: &str) -> String {
+    format!("textures/{}.png", texture)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn models() -> ModelRegistry {
+        let mut assets = AssetManager::new(crate::ASSETS);
+        ModelRegistry::from_ron(&assets.load_string("models/models.ron").unwrap()).unwrap()
+    }
+
+    // loads block definitions from a fresh asset directory, textures come
+    // from the embedded assets
+    pub(crate) fn load(files: &[(&str, &str)]) -> Result<BlockRegistry, BlockError> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "ferrous_blocks_{}_{}", std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed)));
+        fs::create_dir_all(root.join("blocks")).unwrap();
+        for (name, source) in files {
+            fs::write(root.join("blocks").join(name), format!("#![enable(implicit_some)]\n{}", source)).unwrap();
+        }
+        let result = BlockRegistry::load(&mut AssetManager::new(&root), models());
+        fs::remove_dir_all(&root).unwrap();
+        result
+    }
+
+    const STONE: &str = r#"(name: "stone", textures: (all: "stone"), solid: true, hardness: 1.5)"#;
+
+    #[test]
+    fn loads_shipped_blocks() {
+        let blocks = load(&[("stone.ron", STONE)]).unwrap();
+        assert_eq!(blocks.names(), ["air", "stone"]);
+
+        let blocks = BlockRegistry::load(&mut AssetManager::new(crate::ASSETS), models()).unwrap();
+        for (id, name) in blocks.names().iter().enumerate() {
+            assert_eq!(blocks.lookup(name), Some(id as i8));
+        }
+        let water = blocks.id("water").unwrap();
+        assert_eq!(blocks.lookup("water:3"), Some(blocks.fluid_block(water, 3)));
+        assert_eq!(blocks.lookup("air"), Some(AIR));
+        assert_eq!(blocks.lookup("unobtainium"), None);
+    }
+
+    #[test]
+    fn duplicate_name() {
+        let result = load(&[("a.ron", STONE), ("b.ron", STONE)]);
+        assert!(matches!(result, Err(BlockError::DuplicateName { name, path }) if name == "stone" && path == "blocks/b.ron"));
+        let air = r#"(name: "air", textures: (all: "stone"), solid: false, hardness: 0.0)"#;
+        assert!(matches!(load(&[("air.ron", air)]), Err(BlockError::DuplicateName { .. })));
+    }
+
+    #[test]
+    fn missing_texture() {
+        let block = r#"(name: "ore", textures: (side: "stone", top: "ore", bottom: "stone"), solid: true, hardness: 3.0)"#;
+        let result = load(&[("ore.ron", block)]);
+        assert!(matches!(result, Err(BlockError::MissingTexture { name, texture, path })
+            if name == "ore" && texture == "ore" && path == "textures/ore.png"));
+
+        let block = r#"(name: "ore", textures: (side: "stone"), solid: true, hardness: 3.0)"#;
+        let result = load(&[("ore.ron", block)]);
+        assert!(matches!(result, Err(BlockError::MissingFaceTexture { face: Face::Top, .. })));
+    }
+
+    #[test]
+    fn unknown_model() {
+        let block = r#"(name: "pillar", textures: (all: "stone"), model: "pillar", solid: true, hardness: 1.5)"#;
+        let result = load(&[("pillar.ron", block)]);
+        assert!(matches!(result, Err(BlockError::UnknownModel { name, model }) if name == "pillar" && model == "pillar"));
+    }
+
+    #[test]
+    fn invalid_fluid() {
+        for fluid in [
+            "fluid: (flow_distance: 0, tick_interval: 5)",
+            "fluid: (flow_distance: 8, tick_interval: 5)",
+            "fluid: (flow_distance: 4, tick_interval: 5), model: \"slab\"",
+        ] {
+            let block = format!(r#"(name: "oil", textures: (all: "water"), solid: false, hardness: 100.0, {})"#, fluid);
+            let result = load(&[("oil.ron", &block)]);
+            assert!(matches!(result, Err(BlockError::InvalidFluid { name }) if name == "oil"), "{}", fluid);
+        }
+    }
+
+    #[test]
+    fn invalid_properties() {
+        for properties in ["light_emission: 16, hardness: 1.0", "hardness: -1.0"] {
+            let block = format!(r#"(name: "lamp", textures: (all: "stone"), solid: true, {})"#, properties);
+            let result = load(&[("lamp.ron", &block)]);
+            assert!(matches!(result, Err(BlockError::InvalidProperties { name }) if name == "lamp"), "{}", properties);
+        }
+    }
+
+    #[test]
+    fn unknown_tick_block() {
+        let block = r#"(name: "moss", textures: (all: "grass"), solid: true, hardness: 0.5, tick: Spread(onto: "cobble"))"#;
+        let result = load(&[("moss.ron", block)]);
+        assert!(matches!(result, Err(BlockError::UnknownBlock { name, block }) if name == "moss" && block == "cobble"));
+    }
+
+    #[test]
+    fn too_many_blocks() {
+        // ids are i8s and air takes 0
+        let blocks: Vec<(String, String)> = (0..i8::MAX)
+            .map(|i| (format!("{:03}.ron", i), STONE.replace("\"stone\", textures", &format!("\"stone{}\", textures", i))))
+            .collect();
+        let files: Vec<(&str, &str)> = blocks.iter().map(|(p, s)| (p.as_str(), s.as_str())).collect();
+        assert_eq!(load(&files).unwrap().names().len(), 128);
+
+        let extra = STONE.replace("\"stone\", textures", "\"extra\", textures");
+        let mut files = files;
+        files.push(("999.ron", &extra));
+        assert!(matches!(load(&files), Err(BlockError::TooManyBlocks)));
+
+        // a fluid needs an id for each of its levels too
+        let water = r#"(name: "water", textures: (all: "water"), solid: false, hardness: 100.0, fluid: (flow_distance: 7, tick_interval: 5))"#;
+        files.truncate(i8::MAX as usize - 3);
+        files.push(("999.ron", water));
+        assert!(matches!(load(&files), Err(BlockError::TooManyBlocks)));
+    }
+}
@@ -1,83 +1,238 @@
-extern crate glium;
-use glium::glutin::event::{self, VirtualKeyCode, KeyboardInput, ElementState};
-use vecmath::{Vector3, vec3_cross, vec3_scale, vec3_add};
-
-pub struct FirstPersonSettings {
-    pub move_forward: VirtualKeyCode,
-    pub move_backward: VirtualKeyCode,
-    pub strafe_left: VirtualKeyCode,
-    pub strafe_right: VirtualKeyCode,
-    pub fly_up: VirtualKeyCode,
-    pub fly_down: VirtualKeyCode,
-    pub move_faster: VirtualKeyCode,
-    pub speed_horizontal: f32,
-    pub speed_vertical: f32,
+use glium::draw_parameters::DepthTest;
+use vecmath::{Vector3, vec3_cross};
+
+// defaults for a new camera, vertical field of view in radians
+pub const FOV: f32 = std::f32::consts::PI / 3.0;
+pub const ZNEAR: f32 = 0.1;
+pub const ZFAR: f32 = 1024.0;
+// the field of view can be changed within these at runtime, the same
+// range as the settings allow
+pub const MIN_FOV: f32 = 10.0 * std::f32::consts::PI / 180.0;
+pub const MAX_FOV: f32 = 170.0 * std::f32::consts::PI / 180.0;
+// closer than this and depth precision runs out even with reverse-z
+pub const MIN_NEAR: f32 = 0.01;
+// blocks shown top to bottom in an orthographic view
+pub const ORTHOGRAPHIC_HEIGHT: f32 = 64.0;
+// looking straight up or down would flip the camera over
+const MAX_PITCH: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+// how the camera turns with the mouse and look stick, whichever controller
+// is moving it
+pub struct LookSettings {
+    // degrees turned per mouse count
+    pub sensitivity: f32,
+    pub invert_y: bool,
+    // from 0 for none up to just under 1, how much of the last frame's
+    // turning carries into the next one
+    pub smoothing: f32,
+    // degrees per second turned with a gamepad stick at full tilt
+    pub stick_speed: f32,
 }
 
-impl FirstPersonSettings {
-    pub fn keyboard_wasd() -> Self {
+impl Default for LookSettings {
+    fn default() -> Self {
         Self {
-            move_forward: VirtualKeyCode::W,
-            move_backward: VirtualKeyCode::S,
-            strafe_left: VirtualKeyCode::A,
-            strafe_right: VirtualKeyCode::D,
-            fly_up: VirtualKeyCode::Space,
-            fly_down: VirtualKeyCode::C,
-            move_faster: VirtualKeyCode::LShift,
-            speed_horizontal: 0f32,
-            speed_vertical: 0f32,
+            sensitivity: 0.125,
+            invert_y: false,
+            smoothing: 0.0,
+            stick_speed: 180.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Projection {
+    Perspective,
+    // a perspective with no far plane and depth reversed, so 1 is at the near
+    // plane and 0 infinitely far away, which spreads depth precision far
+    // better over long view distances. It's drawn into a float depth buffer
+    // with OpenGL's depth range switched to 0 to 1, see `scene::SceneTarget`
+    ReverseZ,
+    // parallel lines stay parallel, for map and isometric views, `height`
+    // being how many blocks fit top to bottom
+    Orthographic { height: f32 },
+}
+
+impl Projection {
+    // the next one along, for switching between them at runtime
+    pub fn next(self) -> Self {
+        match self {
+            Projection::Perspective => Projection::ReverseZ,
+            Projection::ReverseZ => Projection::Orthographic { height: ORTHOGRAPHIC_HEIGHT },
+            Projection::Orthographic { .. } => Projection::Perspective,
         }
     }
 }
 
+/// The usual OpenGL perspective projection for a left handed view looking
+/// down +z, `aspect` being height over width like `Camera::aspect_ratio`.
+pub fn perspective(fov: f32, aspect: f32, near: f32, far: f32) -> [[f32; 4]; 4] {
+    let f = 1.0 / (fov / 2.0).tan();
+    [
+        [ f * aspect, 0.0,                0.0              , 0.0 ],
+        [    0.0    ,  f ,                0.0              , 0.0 ],
+        [    0.0    , 0.0,      (far + near) / (far - near), 1.0 ],
+        [    0.0    , 0.0, -(2.0 * far * near) / (far - near), 0.0 ],
+    ]
+}
+
+/// A perspective projection with its far plane at infinity and depth going
+/// from 1 at `near` down towards 0, to be used with a `DepthTest::IfMore`
+/// and a 0 to 1 depth range.
+pub fn reverse_z_perspective(fov: f32, aspect: f32, near: f32) -> [[f32; 4]; 4] {
+    let f = 1.0 / (fov / 2.0).tan();
+    [
+        [ f * aspect, 0.0, 0.0 , 0.0 ],
+        [    0.0    ,  f , 0.0 , 0.0 ],
+        [    0.0    , 0.0, 0.0 , 1.0 ],
+        [    0.0    , 0.0, near, 0.0 ],
+    ]
+}
+
+/// An orthographic projection `height` blocks tall, centred on the camera.
+pub fn orthographic(height: f32, aspect: f32, near: f32, far: f32) -> [[f32; 4]; 4] {
+    [
+        [ 2.0 * aspect / height,      0.0    ,               0.0               , 0.0 ],
+        [          0.0         , 2.0 / height,               0.0               , 0.0 ],
+        [          0.0         ,      0.0    ,        2.0 / (far - near)       , 0.0 ],
+        [          0.0         ,      0.0    , -(far + near) / (far - near), 1.0 ],
+    ]
+}
+
 pub struct Camera {
     aspect_ratio: f32,
+    // vertical field of view in radians, and the near and far planes
+    fov: f32,
+    near: f32,
+    far: f32,
+    pub projection: Projection,
     position: Vector3<f32>,
+    // position before the last update, drawn positions are blended from it
+    previous_position: Vector3<f32>,
+    // how far the frame being drawn is between the last two updates
+    alpha: f32,
     pub up: Vector3<f32>,
     pub right: Vector3<f32>,
     pub forward: Vector3<f32>,
-    dx: f64, 
-    dy: f64,
+    // smoothed mouse movement applied in the last frame
+    look_delta: [f32; 2],
     yaw: f32,
     pitch: f32,
-    settings: FirstPersonSettings,
-    keys: Vec<VirtualKeyCode>,
-    velocity: f32,
-    fast_scale: f32, 
+    pub settings: LookSettings,
 }
 
 impl Camera {
     pub fn new(height: f32, width: f32) -> Camera {
         Camera {
             aspect_ratio: height/width, 
+            fov: FOV,
+            near: ZNEAR,
+            far: ZFAR,
+            projection: Projection::Perspective,
             position: [0.0, 2.0, 0.0],
+            previous_position: [0.0, 2.0, 0.0],
+            alpha: 1.0,
             right:   [1f32, 0f32, 0f32],
             up:      [0f32, 1f32, 0f32],
             forward: [0f32, 0f32, 1f32],
-            dx: 0f64,
-            dy: 0f64,
+            look_delta: [0.0, 0.0],
             yaw: 0f32,
             pitch: 0f32,
-            settings: FirstPersonSettings::keyboard_wasd(),
-            keys: Vec::new(),
-            velocity: 1.5f32,
-            fast_scale: 5f32,
+            settings: LookSettings::default(),
         }
     }
 
+    // where the camera is drawn from, between its last two updates
+    pub fn position(&self) -> Vector3<f32> {
+        let (a, b) = (self.previous_position, self.position);
+        [
+            a[0] + (b[0] - a[0]) * self.alpha,
+            a[1] + (b[1] - a[1]) * self.alpha,
+            a[2] + (b[2] - a[2]) * self.alpha,
+        ]
+    }
+
+    // moves the camera straight there, without blending from where it was
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        self.position = position;
+        self.previous_position = position;
+    }
+
+    // set every frame from `FixedTimestep::alpha`
+    pub fn set_interpolation(&mut self, alpha: f32) {
+        self.alpha = alpha;
+    }
+
+    // height over width
+    pub fn aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    // the view matrix without its translation, for things drawn infinitely
+    // far away like the skybox
+    pub fn rotation_matrix(&self) -> [[f32; 4]; 4] {
+        let mut view = self.view_matrix();
+        view[3] = [0.0, 0.0, 0.0, 1.0];
+        view
+    }
+
+    // height over width, set when the window changes size
+    pub fn set_aspect_ratio(&mut self, height: f32, width: f32) {
+        self.aspect_ratio = height / width;
+    }
+
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+
+    // kept between MIN_FOV and MAX_FOV
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(MIN_FOV, MAX_FOV);
+    }
+
+    pub fn near(&self) -> f32 {
+        self.near
+    }
+
+    // reverse-z has no far plane and ignores it
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    // the near plane is kept past MIN_NEAR and the far plane past the near one
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.near = near.max(MIN_NEAR);
+        self.far = far.max(self.near * 2.0);
+    }
+
     pub fn perspective_matrix(&self) -> [[f32 ;4]; 4] {
-        let fov: f32 = 3.141592 / 3.0;
-        let zfar = 1024.0;
-        let znear = 0.1;
+        match self.projection {
+            Projection::Perspective => perspective(self.fov, self.aspect_ratio, self.near, self.far),
+            Projection::ReverseZ => reverse_z_perspective(self.fov, self.aspect_ratio, self.near),
+            Projection::Orthographic { height } => orthographic(height, self.aspect_ratio, self.near, self.far),
+        }
+    }
 
-        let f = 1.0 / (fov / 2.0).tan();
+    // the sky is infinitely far away, so in an orthographic view it would
+    // shrink to nothing and is drawn with a perspective instead
+    pub fn sky_perspective_matrix(&self) -> [[f32; 4]; 4] {
+        match self.projection {
+            Projection::Orthographic { .. } => perspective(self.fov, self.aspect_ratio, self.near, self.far),
+            _ => self.perspective_matrix(),
+        }
+    }
 
-        [
-            [ f * self.aspect_ratio, 0.0,              0.0              ,  0.0 ],
-            [       0.0            ,  f ,              0.0              ,  0.0 ],
-            [       0.0            , 0.0,  (zfar+znear)/(zfar-znear)    ,  1.0 ],
-            [       0.0            , 0.0, -(2.0*zfar*znear)/(zfar-znear),  0.0 ],
-        ]
+    pub fn is_reverse_z(&self) -> bool {
+        self.projection == Projection::ReverseZ
+    }
+
+    // how depth is tested and what it's cleared to, reverse-z flips both
+    pub fn depth_test(&self) -> DepthTest {
+        if self.is_reverse_z() { DepthTest::IfMore } else { DepthTest::IfLess }
+    }
+
+    pub fn clear_depth(&self) -> f32 {
+        if self.is_reverse_z() { 0.0 } else { 1.0 }
     }
 
     pub fn view_matrix(&self) -> [[f32; 4]; 4] {
@@ -104,9 +259,10 @@ impl Camera {
                  s_norm[2] * f[0] - s_norm[0] * f[2],
                  s_norm[0] * f[1] - s_norm[1] * f[0]];
 
-        let p = [-self.position[0] * s[0] - self.position[1] * s_norm[1] - self.position[2] * s_norm[2],
-                 -self.position[0] * u[0] - self.position[1] *    u[1]   - self.position[2] * u[2],
-                 -self.position[0] * f[0] - self.position[1] *    f[1]   - self.position[2] * f[2]];
+        let position = self.position();
+        let p = [-position[0] * s[0] - position[1] * s_norm[1] - position[2] * s_norm[2],
+                 -position[0] * u[0] - position[1] *    u[1]   - position[2] * u[2],
+                 -position[0] * f[0] - position[1] *    f[1]   - position[2] * f[2]];
  
         [
             [s_norm[0], u[0], f[0], 0.0],
@@ -116,38 +272,66 @@ impl Camera {
         ] 
     }
 
-    pub fn update(&mut self, t: f32) {
-        self.update_direction();
-        self.update_pos(t);
+    // call at the start of every simulation step, before a controller moves
+    // the camera, so the frames drawn until the next one blend from here
+    pub fn begin_step(&mut self) {
+        self.previous_position = self.position;
+    }
 
-        // math debugging
-        //print!("\x1B[2J\x1B[1;1H");
-        //println!("dx, dy:         [{:?}, {:?}]", self.dx, self.dy);
-        //println!("forward:        {:?}", self.forward);
-        //println!("right:          {:?}", self.right);
-        //println!("{}", self.keys.len());
+    // where the camera is as of the last simulation step, for controllers
+    pub fn simulated_position(&self) -> Vector3<f32> {
+        self.position
     }
 
-    fn update_direction(&mut self) {
-        let pi: f32 = std::f32::consts::PI;
-        let _0 = 0f32;
-        let _1 = 1f32;
-        let _2 = _1 + _1;
-        let _3 = _2 + _1;
-        let _4 = _3 + _1;
-        let _360 = 360f32;
-        let dy: f32 = self.dy as f32;
-        let dx: f32 = self.dx as f32;
+    // moves the camera during a simulation step, drawn frames blend towards it
+    pub fn move_to(&mut self, position: Vector3<f32>) {
+        self.position = position;
+    }
 
-        self.yaw = (self.yaw - dx / _360 * pi / _4) % (_2 * pi);
-        self.pitch = self.pitch + dy / _360 * pi / _4;
-        self.pitch = self.pitch.min(pi / _2).max(-pi / _2);
-        
-        //println!("{:?}, {:?}", self.yaw, self.pitch);
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
 
+    pub fn set_orientation(&mut self, yaw: f32, pitch: f32) {
+        let tau = 2.0 * std::f32::consts::PI;
+        self.yaw = yaw.rem_euclid(tau);
+        self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
         self.set_yaw_pitch();
     }
 
+    pub fn turn(&mut self, yaw: f32, pitch: f32) {
+        self.set_orientation(self.yaw + yaw, self.pitch + pitch);
+    }
+
+    /// How far to turn this frame, as radians of yaw and pitch, from the raw
+    /// mouse movement and how far a look stick is tilted with up and right
+    /// positive. This runs every frame rather than every step so looking
+    /// around never lags behind, `dt` being the frame time in seconds.
+    pub fn look_angles(&mut self, mouse: (f64, f64), stick: [f32; 2], dt: f32) -> [f32; 2] {
+        let settings = &self.settings;
+        // the stick turns at a steady rate, worked out in mouse counts
+        let counts = settings.stick_speed * dt / settings.sensitivity.max(f32::EPSILON);
+        let delta = (mouse.0 + (stick[0] * counts) as f64, mouse.1 - (stick[1] * counts) as f64);
+        // mouse y grows downwards
+        let dy = if settings.invert_y { delta.1 } else { -delta.1 };
+        let raw = [delta.0 as f32, dy as f32];
+
+        // the share kept is per sixtieth of a second so smoothing feels the
+        // same at any frame rate
+        let keep = settings.smoothing.clamp(0.0, 0.99).powf(dt * 60.0);
+        self.look_delta = [
+            raw[0] + (self.look_delta[0] - raw[0]) * keep,
+            raw[1] + (self.look_delta[1] - raw[1]) * keep,
+        ];
+
+        let radians = settings.sensitivity.to_radians();
+        [-self.look_delta[0] * radians, self.look_delta[1] * radians]
+    }
+
     fn set_yaw_pitch(&mut self) {
         let (y_s, y_c, p_s, p_c) = (self.yaw.sin(), self.yaw.cos(), self.pitch.sin(), self.pitch.cos());
         self.forward = [y_s * p_c, p_s, y_c * p_c];
@@ -157,68 +341,122 @@ impl Camera {
 
     fn update_right(&mut self) {
         self.right = vec3_cross(self.up, self.forward);
-        self.dx = 0.0;
-        self.dy = 0.0;
-    }
-
-    fn update_pos(&mut self, t: f32) {
-        macro_rules! displace {
-            ($x:expr, $y:expr, $z:expr) => (
-                if self.keys.contains($z) {
-                    let displacment = vec3_scale($x, $y);
-                    self.position = vec3_add(displacment, self.position);
-                }
-            );
-        }
+    }
+}
 
-        let mut dist = self.velocity * t;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vecmath::col_mat4_transform;
 
-        if self.keys.contains(&self.settings.move_faster) {
-            dist *= self.fast_scale;
+    fn assert_matrix(a: [[f32; 4]; 4], b: [[f32; 4]; 4]) {
+        for col in 0..4 {
+            for row in 0..4 {
+                assert!((a[col][row] - b[col][row]).abs() < 1e-6, "{:?} != {:?}", a, b);
+            }
         }
+    }
 
-        displace!(self.forward, -dist, &self.settings.move_backward);
-        displace!(self.forward, dist, &self.settings.move_forward);
-
-        displace!(self.right, dist, &self.settings.strafe_left);
-        displace!(self.right, -dist, &self.settings.strafe_right);
-
-        displace!(self.up, dist, &self.settings.fly_up);
-        displace!(self.up, -dist, &self.settings.fly_down);
-    }
-
-    pub fn parse_input(&mut self, e: &event::DeviceEvent) {
-        match e {
-            event::DeviceEvent::Key(KeyboardInput { state, virtual_keycode, .. }) => {
-                let virtual_keycode = virtual_keycode.unwrap();
-                match state {
-                    ElementState::Pressed => {
-                        if !self.keys.contains(&virtual_keycode) {
-                            self.keys.push(virtual_keycode);
-                        }
-                    },
-                    ElementState::Released => {
-                        if self.keys.contains(&virtual_keycode) {
-                            let i = self.keys.iter().position(|&r| r == virtual_keycode).unwrap();
-                            self.keys.remove(i);
-                        }
-                    },
-                }
-            },
-            //event::DeviceEvent::MouseMotion { delta: (ref x, ref y) } => {
-            //    self.dx = *x;
-            //    self.dy = -y;
-            //},
-            _ => {},
-        }
+    // normalised device depth of a point `z` in front of the camera
+    fn depth(matrix: [[f32; 4]; 4], z: f32) -> f32 {
+        let clip = col_mat4_transform(matrix, [0.0, 0.0, z, 1.0]);
+        clip[2] / clip[3]
+    }
+
+    #[test]
+    fn perspective_matrix() {
+        let matrix = perspective(std::f32::consts::PI / 2.0, 0.5, 1.0, 3.0);
+        assert_matrix(matrix, [
+            [0.5, 0.0,  0.0, 0.0],
+            [0.0, 1.0,  0.0, 0.0],
+            [0.0, 0.0,  2.0, 1.0],
+            [0.0, 0.0, -3.0, 0.0],
+        ]);
+        assert!((depth(matrix, 1.0) + 1.0).abs() < 1e-6);
+        assert!((depth(matrix, 3.0) - 1.0).abs() < 1e-6);
+
+        // the edges of the view land on the edges of the screen
+        let clip = col_mat4_transform(matrix, [4.0, 2.0, 2.0, 1.0]);
+        assert!((clip[0] / clip[3] - 1.0).abs() < 1e-6);
+        assert!((clip[1] / clip[3] - 1.0).abs() < 1e-6);
     }
 
-    pub fn parse_cursor(&mut self, e: &event::WindowEvent) {
-        match e {
-            event::WindowEvent::CursorMoved { position, ..} => {
-                
-            },
-            _ => {},
+    #[test]
+    fn reverse_z_perspective_matrix() {
+        let matrix = reverse_z_perspective(std::f32::consts::PI / 2.0, 0.5, 1.0);
+        assert_matrix(matrix, [
+            [0.5, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ]);
+        // the near plane is at 1 and depth falls towards 0 with no far plane
+        assert!((depth(matrix, 1.0) - 1.0).abs() < 1e-6);
+        assert!((depth(matrix, 4.0) - 0.25).abs() < 1e-6);
+        assert!(depth(matrix, 1e6) > 0.0 && depth(matrix, 1e6) < 1e-5);
+        // further is always smaller, for DepthTest::IfMore
+        assert!(depth(matrix, 1000.0) < depth(matrix, 999.0));
+
+        // x and y are the same as the usual perspective
+        let clip = col_mat4_transform(matrix, [4.0, 2.0, 2.0, 1.0]);
+        assert!((clip[0] / clip[3] - 1.0).abs() < 1e-6);
+        assert!((clip[1] / clip[3] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn orthographic_matrix() {
+        let matrix = orthographic(4.0, 0.5, 1.0, 3.0);
+        assert_matrix(matrix, [
+            [0.25, 0.0,  0.0, 0.0],
+            [0.0,  0.5,  0.0, 0.0],
+            [0.0,  0.0,  1.0, 0.0],
+            [0.0,  0.0, -2.0, 1.0],
+        ]);
+        assert!((depth(matrix, 1.0) + 1.0).abs() < 1e-6);
+        assert!((depth(matrix, 3.0) - 1.0).abs() < 1e-6);
+        // no perspective divide, the size doesn't change with distance
+        for z in [1.0, 2.0, 3.0] {
+            let clip = col_mat4_transform(matrix, [4.0, 2.0, z, 1.0]);
+            assert_eq!(clip[3], 1.0);
+            assert!((clip[0] - 1.0).abs() < 1e-6 && (clip[1] - 1.0).abs() < 1e-6);
         }
     }
+
+    #[test]
+    fn runtime_settings() {
+        let mut camera = Camera::new(1080.0, 1920.0);
+        camera.set_fov(1.0);
+        assert_eq!(camera.fov(), 1.0);
+        camera.set_fov(10.0);
+        assert_eq!(camera.fov(), MAX_FOV);
+        camera.set_fov(0.0);
+        assert_eq!(camera.fov(), MIN_FOV);
+        assert_matrix(camera.perspective_matrix(), perspective(MIN_FOV, 1080.0 / 1920.0, ZNEAR, ZFAR));
+
+        camera.set_clip_planes(0.5, 100.0);
+        assert_eq!((camera.near(), camera.far()), (0.5, 100.0));
+        assert_matrix(camera.perspective_matrix(), perspective(MIN_FOV, 1080.0 / 1920.0, 0.5, 100.0));
+        camera.set_clip_planes(0.0, 0.0);
+        assert_eq!(camera.near(), MIN_NEAR);
+        assert!(camera.far() > camera.near());
+    }
+
+    #[test]
+    fn camera_projections() {
+        let mut camera = Camera::new(1080.0, 1920.0);
+        assert_matrix(camera.perspective_matrix(), perspective(FOV, 1080.0 / 1920.0, ZNEAR, ZFAR));
+        assert_eq!(camera.depth_test(), DepthTest::IfLess);
+        assert_eq!(camera.clear_depth(), 1.0);
+        camera.projection = camera.projection.next();
+        assert_eq!(camera.projection, Projection::ReverseZ);
+        assert_matrix(camera.perspective_matrix(), reverse_z_perspective(FOV, 1080.0 / 1920.0, ZNEAR));
+        assert_eq!(camera.depth_test(), DepthTest::IfMore);
+        assert_eq!(camera.clear_depth(), 0.0);
+        camera.projection = camera.projection.next();
+        assert_eq!(camera.projection, Projection::Orthographic { height: ORTHOGRAPHIC_HEIGHT });
+        assert_matrix(camera.perspective_matrix(), orthographic(ORTHOGRAPHIC_HEIGHT, 1080.0 / 1920.0, ZNEAR, ZFAR));
+        // the sky still gets a perspective
+        assert_matrix(camera.sky_perspective_matrix(), perspective(FOV, 1080.0 / 1920.0, ZNEAR, ZFAR));
+        assert_eq!(camera.projection.next(), Projection::Perspective);
+    }
 }
@@ -1,129 +1,285 @@
 extern crate glium;
 extern crate vecmath;
 
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::block::{self, BlockRegistry, RenderLayer};
 use crate::cubemesh::*;
+use crate::model::{BakedModel, MODEL_UNITS};
+use crate::visibility::Face;
+
+pub const CHUNK_SIZE: usize = 32;
+
+pub type VoxelMap = [[[i8; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+
+// meshes are fully lit until there is a lighting engine
+const MAX_LIGHT: u8 = 15;
 
-const CHUNK_SIZE: usize = 32;
 pub struct ChunkMesh {
-    pub vertices: Vec<Vertex>,
+    pub vertices: Vec<PackedVertex>,
+    // opaque and cutout faces first, then the translucent faces
     pub indices: Vec<u32>, 
-    voxel_map: [[[i8; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+    opaque_index_count: usize,
+    // chunk local centre of each translucent face, in index order
+    translucent_centers: Vec<[f32; 3]>,
+    translucent_indices: Vec<u32>,
+    voxel_map: VoxelMap,
 }
 
 impl Default for ChunkMesh {
     fn default() -> ChunkMesh {
-        let vox: [[[i8; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE] = [[[1; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        let vox: VoxelMap = [[[1; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
         ChunkMesh {
             vertices: Vec::new(),
             indices: Vec::new(),
+            opaque_index_count: 0,
+            translucent_centers: Vec::new(),
+            translucent_indices: Vec::new(),
             voxel_map: vox, 
         }
     }
 }
 
 impl ChunkMesh {
-    pub fn new() -> ChunkMesh {
+    pub fn from_voxels(voxel_map: VoxelMap) -> ChunkMesh {
         ChunkMesh {
-           ..Default::default() 
+            voxel_map,
+            ..Default::default()
         }
     }
 
-    pub fn generate_mesh(&mut self) {        
-        for x in 0..CHUNK_SIZE {
-            for y in 0..CHUNK_SIZE {
-                for z in 0..CHUNK_SIZE {
-                    if self.voxel_map[x][y][z] != 0 {
-                        let matrix = [
-                            [ 1.0, 0.0, 0.0, 0.0 ],
-                            [ 0.0, 1.0, 0.0, 0.0 ],
-                            [ 0.0, 0.0, 1.0, 0.0 ],
-                            [ x as f32 ,  y as f32, z as f32, 1.0f32 ]
-                        ];
-                        self.draw_cube(matrix, x, y, z);
+    pub fn voxels(&self) -> &VoxelMap {
+        &self.voxel_map
+    }
+
+    // the mesh has to be generated again to show the change
+    pub fn set_voxel(&mut self, x: usize, y: usize, z: usize, voxel: i8) {
+        self.voxel_map[x][y][z] = voxel;
+    }
+
+    pub fn generate_mesh(&mut self, blocks: &BlockRegistry) {        
+        self.generate_mesh_scaled(1, blocks);
+    }
+
+    // meshes the voxel map as cubes of `scale` voxels, used for LOD meshes whose
+    // voxel map has already been downsampled into uniform blocks. Block models
+    // are only baked in at full resolution, LOD meshes draw them as cubes
+    pub fn generate_mesh_scaled(&mut self, scale: usize, blocks: &BlockRegistry) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.translucent_centers.clear();
+        self.translucent_indices.clear();
+        for x in (0..CHUNK_SIZE).step_by(scale) {
+            for y in (0..CHUNK_SIZE).step_by(scale) {
+                for z in (0..CHUNK_SIZE).step_by(scale) {
+                    let voxel = self.voxel_map[x][y][z];
+                    if voxel == block::AIR {
+                        continue;
+                    }
+                    match blocks.model(voxel) {
+                        Some(model) if scale == 1 => self.draw_model(x as i32, y as i32, z as i32, model, blocks),
+                        _ if scale == 1 && blocks.fluid(voxel).is_some() => {
+                            self.draw_fluid(x as i32, y as i32, z as i32, blocks)
+                        },
+                        _ => self.draw_cube(x as i32, y as i32, z as i32, scale as i32, blocks),
                     }
                 }
             }
         }
+        self.opaque_index_count = self.indices.len();
+        let translucent = std::mem::take(&mut self.translucent_indices);
+        self.indices.extend(translucent);
     }
 
-    fn draw_cube(
-        &mut self, 
-        matrix: vecmath::Matrix4<f32>, 
-        x: usize, 
-        y: usize, 
-        z: usize
-    ) 
-    {
-        // cast to signed ints to avoid underflow
-        let (x, y, z) = (x as i32, y as i32, z as i32);
-
-        // east face
-        if self.get_voxel(x+1, y, z) == 0 {
-            self.push_vertex(EAST, matrix);
-        }    
-
-        // west face
-        if self.get_voxel(x-1, y, z) == 0 {
-            self.push_vertex(WEST, matrix);
-        }    
-
-        // top face
-        if self.get_voxel(x, y+1, z) == 0 {
-            self.push_vertex(TOP, matrix);
-        }    
-
-        //bottom face
-        if self.get_voxel(x, y-1, z) == 0 {
-            self.push_vertex(BOTTOM, matrix);
-        }    
-
-        // north face (pls don't sue me)
-        if self.get_voxel(x, y, z+1) == 0 {
-            self.push_vertex(NORTH, matrix);
-        }    
-
-        //south face
-        if self.get_voxel(x, y, z-1) == 0 {
-            self.push_vertex(SOUTH, matrix);
-        }    
-    }
-
-    fn push_vertex (&mut self, face: [Vertex;4], matrix: vecmath::Matrix4<f32>) {
-        for vertex in face {
-            let mut vertex = vertex.clone();
-            vertex.transform(matrix);
-            self.vertices.push(vertex);
+    pub fn opaque_range(&self) -> Range<usize> {
+        0..self.opaque_index_count
+    }
+
+    pub fn translucent_range(&self) -> Range<usize> {
+        self.opaque_index_count..self.indices.len()
+    }
+
+    /// The translucent index range reordered so faces furthest from `eye`
+    /// (in chunk local space) are drawn first.
+    pub fn sorted_translucent_indices(&self, eye: [f32; 3]) -> Vec<u32> {
+        let distance = |c: &[f32; 3]| {
+            (c[0] - eye[0]).powi(2) + (c[1] - eye[1]).powi(2) + (c[2] - eye[2]).powi(2)
+        };
+        let mut order: Vec<usize> = (0..self.translucent_centers.len()).collect();
+        order.sort_by(|&a, &b| {
+            distance(&self.translucent_centers[b])
+                .partial_cmp(&distance(&self.translucent_centers[a]))
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let translucent = &self.indices[self.translucent_range()];
+        order
+            .iter()
+            .flat_map(|&face| translucent[face * 6..face * 6 + 6].iter().copied())
+            .collect()
+    }
+
+    fn draw_cube(&mut self, x: i32, y: i32, z: i32, scale: i32, blocks: &BlockRegistry) {
+        // faces on the chunk border are always drawn, which doubles as a skirt
+        // hiding the cracks between neighbouring chunks of different LODs
+        let block = self.get_voxel(x, y, z);
+        for face in Face::ALL {
+            let [dx, dy, dz] = face.offset();
+            let neighbour = self.get_voxel(x + dx * scale, y + dy * scale, z + dz * scale);
+            if blocks.face_visible(block, neighbour, face) {
+                self.push_face(face, [x, y, z], scale, MODEL_UNITS as i32, blocks);
+            }
         }
-        self.push_indices();
     }
 
-    fn push_indices(&mut self) {
-        for _i in 0..6 {
-            let last_index = self.get_last_index();
-            for index in INDICES {
-                self.indices.push(index + last_index);
+    // a cube with its top lowered to the fluid's level
+    fn draw_fluid(&mut self, x: i32, y: i32, z: i32, blocks: &BlockRegistry) {
+        let block = self.get_voxel(x, y, z);
+        let source = blocks.fluid(block).map(|f| f.source);
+        // fluid with more of itself on top fills the whole block
+        let covered = blocks.fluid(self.get_voxel(x, y + 1, z)).map(|f| f.source) == source;
+        let height = if covered { MODEL_UNITS as i32 } else { blocks.fluid_height(block).unwrap() };
+        for face in Face::ALL {
+            let [dx, dy, dz] = face.offset();
+            let neighbour = self.get_voxel(x + dx, y + dy, z + dz);
+            // the lowered surface can be seen under whatever is above it
+            let visible = match face {
+                Face::Top => !covered,
+                _ => blocks.face_visible(block, neighbour, face),
+            };
+            if visible {
+                self.push_face(face, [x, y, z], 1, height, blocks);
             }
         }
     }
 
-    // clone the last index to a new value
-    fn get_last_index(&self) -> u32 {
-        if self.indices.is_empty() {
-            let last_index: u32 = 0;
-            return last_index;
+    fn draw_model(&mut self, x: i32, y: i32, z: i32, model: &BakedModel, blocks: &BlockRegistry) {
+        let block = self.get_voxel(x, y, z);
+        let units = MODEL_UNITS as i32;
+        for quad in model.quads.iter() {
+            if let Some(cullface) = quad.cullface {
+                let [dx, dy, dz] = cullface.offset();
+                if !blocks.face_visible(block, self.get_voxel(x + dx, y + dy, z + dz), cullface) {
+                    continue;
+                }
+            }
+
+            let mut positions = [[0; 3]; 4];
+            let mut uvs = [[0; 2]; 4];
+            for i in 0..4 {
+                for axis in 0..3 {
+                    let local = quad.positions[i][axis].round() as i32;
+                    positions[i][axis] = ([x, y, z][axis] * units + local).max(0) as u16;
+                }
+                uvs[i] = [quad.uvs[i][0].round() as u8, quad.uvs[i][1].round() as u8];
+            }
+            // partial blocks aren't ambient occluded
+            self.push_quad(quad.face, positions, uvs, [3; 4], 0, block, blocks);
+        }
+    }
+
+    // `height` is how far up the block the face goes in model units, used
+    // to lower the top of fluids
+    fn push_face(&mut self, face: Face, origin: [i32; 3], scale: i32, height: i32, blocks: &BlockRegistry) {
+        let block = self.get_voxel(origin[0], origin[1], origin[2]);
+        let units = MODEL_UNITS as i32;
+        let mut positions = [[0; 3]; 4];
+        let mut uvs = [[0; 2]; 4];
+        let mut aos = [0; 4];
+        for (i, vertex) in face_vertices(face).iter().enumerate() {
+            let corner = vertex.corner();
+            for axis in 0..3 {
+                positions[i][axis] = ((origin[axis] + corner[axis] as i32 * scale) * units) as u16;
+            }
+            positions[i][1] = ((origin[1] * units) + corner[1] as i32 * scale * height) as u16;
+            // v runs up the side faces, so they show the part of the texture below the top
+            let uv_corner = vertex.uv_corner();
+            let v = match face {
+                Face::Top | Face::Bottom => uv_corner[1] * units as u8,
+                _ => uv_corner[1] * height as u8,
+            };
+            uvs[i] = [uv_corner[0] * units as u8, v];
+            aos[i] = self.vertex_ao(face, origin, corner, scale, blocks);
+        }
+        self.push_quad(face, positions, uvs, aos, scale.trailing_zeros() as u8, block, blocks);
+    }
+
+    // positions and uvs are in model units
+    #[allow(clippy::too_many_arguments)]
+    fn push_quad(
+        &mut self,
+        face: Face,
+        positions: [[u16; 3]; 4],
+        uvs: [[u8; 2]; 4],
+        aos: [u8; 4],
+        scale_log2: u8,
+        block: i8,
+        blocks: &BlockRegistry,
+    ) {
+        let first_vertex = self.vertices.len() as u32;
+        for i in 0..4 {
+            self.vertices.push(PackedVertex::pack(
+                positions[i],
+                face,
+                uvs[i],
+                scale_log2,
+                aos[i],
+                blocks.texture_layer(block, face),
+                MAX_LIGHT,
+            ));
+        }
+
+        if blocks.render_layer(block) == Some(RenderLayer::Translucent) {
+            let mut center = [0.0; 3];
+            for position in positions.iter() {
+                for axis in 0..3 {
+                    center[axis] += position[axis] as f32 / MODEL_UNITS / 4.0;
+                }
+            }
+            self.translucent_centers.push(center);
+            self.translucent_indices.extend(INDICES.iter().map(|i| i + first_vertex));
+        } else {
+            self.indices.extend(INDICES.iter().map(|i| i + first_vertex));
+        }
+    }
+
+    // classic voxel ambient occlusion, 3 is fully lit and 0 is a fully occluded corner
+    fn vertex_ao(&self, face: Face, origin: [i32; 3], corner: [u8; 3], scale: i32, blocks: &BlockRegistry) -> u8 {
+        let normal = face.offset();
+        let axis = normal.iter().position(|&n| n != 0).unwrap();
+        let (t1, t2) = ((axis + 1) % 3, (axis + 2) % 3);
+
+        // the block layer in front of the face
+        let mut front = origin;
+        for i in 0..3 {
+            front[i] += normal[i] * scale;
+        }
+        let solid = |offsets: [(usize, i32); 2]| {
+            let mut p = front;
+            for (axis, step) in offsets {
+                p[axis] += step * scale;
+            }
+            blocks.is_opaque(self.get_voxel(p[0], p[1], p[2])) as u8
+        };
+
+        let d1 = corner[t1] as i32 * 2 - 1;
+        let d2 = corner[t2] as i32 * 2 - 1;
+        let side1 = solid([(t1, d1), (t2, 0)]);
+        let side2 = solid([(t1, 0), (t2, d2)]);
+        if side1 == 1 && side2 == 1 {
+            return 0;
         }
-        let last_index = self.indices.last().unwrap();
-        let mut last_index = last_index.clone();
-        last_index += 1;
-        last_index
+        3 - side1 - side2 - solid([(t1, d1), (t2, d2)])
     }
 
     // this is a safe function that protects against out of bounds errors
-    fn get_voxel(&self, x: i32, y: i32, z: i32) -> i8 {
+    pub fn get_voxel(&self, x: i32, y: i32, z: i32) -> i8 {
         if x < 0 || y < 0 || z < 0 {
             return 0;
         }
-        if x > 31 || y > 31 || z > 31 {
+        let max = CHUNK_SIZE as i32 - 1;
+        if x > max || y > max || z > max {
             return 0;
         }
         self.voxel_map[x as usize][y as usize][z as usize]
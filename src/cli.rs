@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::settings::{Settings, SETTINGS};
+
+/// Explore a voxel world rendered with OpenGL.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// Seed a new world is generated from, 0 if not given. Saved worlds keep
+    /// the seed they were made with
+    #[arg(long)]
+    pub seed: Option<u32>,
+
+    /// Load the world from this file if it exists, and save it there on quit
+    #[arg(long, value_name = "PATH")]
+    pub save: Option<PathBuf>,
+
+    /// Where the camera starts
+    #[arg(long, value_name = "X,Y,Z", value_parser = parse_position, default_value = "0,80,0")]
+    pub position: [f32; 3],
+
+    /// Chunks drawn around the camera, instead of the one in the settings
+    #[arg(long, value_name = "CHUNKS")]
+    pub render_distance: Option<u32>,
+
+    /// Window size, instead of the one in the settings
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size)]
+    pub window_size: Option<(u32, u32)>,
+
+    /// Settings file, written with the defaults if it doesn't exist
+    #[arg(long, value_name = "PATH", default_value = SETTINGS)]
+    pub settings: PathBuf,
+
+    /// Play the camera timeline at PATH, print frame time statistics and quit
+    #[arg(long, value_name = "PATH")]
+    pub benchmark: Option<PathBuf>,
+}
+
+impl Args {
+    // the options that stand in for settings, only for this run
+    pub fn override_settings(&self, settings: &mut Settings) {
+        if let Some(distance) = self.render_distance {
+            settings.graphics.render_distance = distance;
+        }
+        if let Some((width, height)) = self.window_size {
+            settings.window.width = width;
+            settings.window.height = height;
+        }
+    }
+}
+
+fn parse_position(value: &str) -> Result<[f32; 3], String> {
+    let coordinates = value
+        .split(',')
+        .map(|c| c.trim().parse::<f32>().map_err(|e| format!("{}: {}", c, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    coordinates.try_into().map_err(|_| "expected three coordinates like 0,80,0".to_string())
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value.split_once('x').ok_or("expected a size like 1280x720")?;
+    let parse = |n: &str| n.trim().parse::<u32>().map_err(|e| format!("{}: {}", n, e));
+    Ok((parse(width)?, parse(height)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        assert_eq!(parse_position("0,80,0"), Ok([0.0, 80.0, 0.0]));
+        assert_eq!(parse_position(" -1.5, 2 ,3e2"), Ok([-1.5, 2.0, 300.0]));
+        for bad in ["", "1,2", "1,2,3,4", "1,,3", "a,b,c", "1;2;3"] {
+            assert!(parse_position(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1280x720"), Ok((1280, 720)));
+        assert_eq!(parse_size(" 800 x 600 "), Ok((800, 600)));
+        for bad in ["", "1280", "1280*720", "x720", "1280x", "-1x720", "1280x720x2", "wide x tall"] {
+            assert!(parse_size(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn arguments() {
+        let args = Args::try_parse_from(["ferrous-opengl", "--seed", "5", "--window-size", "640x480"]).unwrap();
+        assert_eq!(args.seed, Some(5));
+        assert_eq!(args.position, [0.0, 80.0, 0.0]);
+        let mut settings = Settings::default();
+        args.override_settings(&mut settings);
+        assert_eq!((settings.window.width, settings.window.height), (640, 480));
+
+        assert!(Args::try_parse_from(["ferrous-opengl", "--position", "1,2"]).is_err());
+        assert_eq!(Args::try_parse_from(["ferrous-opengl"]).unwrap().seed, None);
+    }
+}
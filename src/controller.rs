@@ -0,0 +1,316 @@
+use std::f32::consts::PI;
+
+use vecmath::{vec3_add, vec3_len, vec3_scale, vec3_sub, Vector3};
+
+use crate::block::BlockRegistry;
+use crate::camera::Camera;
+use crate::input::{Action, Input};
+use crate::spline;
+use crate::tick::VoxelGrid;
+use crate::world::World;
+
+/// What a controller can look at while moving the camera.
+pub struct ControllerContext<'a> {
+    pub input: &'a Input,
+    pub world: &'a World,
+    pub blocks: &'a BlockRegistry,
+}
+
+/// Something that moves the camera, switched between at runtime.
+pub trait CameraController {
+    fn name(&self) -> &'static str;
+
+    // called when switched to, to carry on from wherever the camera is
+    fn activate(&mut self, _camera: &mut Camera, _context: &ControllerContext) {}
+
+    // turns by a frame's look input, in radians of yaw and pitch
+    fn look(&mut self, camera: &mut Camera, turn: [f32; 2]) {
+        camera.turn(turn[0], turn[1]);
+    }
+
+    // runs once per simulation step of `t` seconds, after `Camera::begin_step`
+    fn update(&mut self, camera: &mut Camera, context: &ControllerContext, t: f32);
+}
+
+// the actions that move the camera, their keys are set in bindings.ron
+pub struct FirstPersonSettings {
+    pub move_forward: Action,
+    pub move_backward: Action,
+    pub strafe_left: Action,
+    pub strafe_right: Action,
+    pub fly_up: Action,
+    pub fly_down: Action,
+    pub move_faster: Action,
+    // blocks per second, and how much faster it is with move_faster held
+    pub velocity: f32,
+    pub fast_scale: f32,
+}
+
+impl Default for FirstPersonSettings {
+    fn default() -> Self {
+        Self {
+            move_forward: Action::MoveForward,
+            move_backward: Action::MoveBackward,
+            strafe_left: Action::StrafeLeft,
+            strafe_right: Action::StrafeRight,
+            fly_up: Action::FlyUp,
+            fly_down: Action::FlyDown,
+            move_faster: Action::MoveFaster,
+            velocity: 1.5,
+            fast_scale: 5.0,
+        }
+    }
+}
+
+impl FirstPersonSettings {
+    // forward and sideways movement from -1 to 1, sideways being positive
+    // towards the camera's `right`
+    fn movement(&self, input: &Input) -> (f32, f32) {
+        (
+            input.value(self.move_forward) - input.value(self.move_backward),
+            input.value(self.strafe_left) - input.value(self.strafe_right),
+        )
+    }
+}
+
+/// Flies freely in whatever direction the camera faces, through blocks.
+#[derive(Default)]
+pub struct FlyController {
+    pub settings: FirstPersonSettings,
+}
+
+impl CameraController for FlyController {
+    fn name(&self) -> &'static str {
+        "fly"
+    }
+
+    fn update(&mut self, camera: &mut Camera, context: &ControllerContext, t: f32) {
+        let input = context.input;
+        let mut dist = self.settings.velocity * t;
+        if input.is_held(self.settings.move_faster) {
+            dist *= self.settings.fast_scale;
+        }
+
+        let (forward, sideways) = self.settings.movement(input);
+        let up = input.value(self.settings.fly_up) - input.value(self.settings.fly_down);
+        let mut position = camera.simulated_position();
+        position = vec3_add(position, vec3_scale(camera.forward, forward * dist));
+        position = vec3_add(position, vec3_scale(camera.right, sideways * dist));
+        position = vec3_add(position, vec3_scale(camera.up, up * dist));
+        camera.move_to(position);
+    }
+}
+
+// size of the player's collision box and where the camera sits in it
+const PLAYER_HALF_WIDTH: f32 = 0.3;
+const PLAYER_HEIGHT: f32 = 1.8;
+const EYE_HEIGHT: f32 = 1.62;
+// in blocks per second, and per second squared for gravity
+const WALK_SPEED: f32 = 4.3;
+const JUMP_SPEED: f32 = 8.0;
+const GRAVITY: f32 = 25.0;
+const TERMINAL_VELOCITY: f32 = 50.0;
+// gap kept between the player and the blocks it's pushed against
+const SKIN: f32 = 1e-3;
+
+/// Walks on the ground with gravity and collision, jumping with fly_up.
+#[derive(Default)]
+pub struct WalkController {
+    pub settings: FirstPersonSettings,
+    velocity: Vector3<f32>,
+    on_ground: bool,
+}
+
+impl WalkController {
+    // whether a player box with its feet at `feet` is inside a solid block
+    fn collides(feet: Vector3<f32>, context: &ControllerContext) -> bool {
+        let min = [feet[0] - PLAYER_HALF_WIDTH, feet[1], feet[2] - PLAYER_HALF_WIDTH];
+        let max = [feet[0] + PLAYER_HALF_WIDTH, feet[1] + PLAYER_HEIGHT, feet[2] + PLAYER_HALF_WIDTH];
+        for x in min[0].floor() as i32..=max[0].floor() as i32 {
+            for y in min[1].floor() as i32..=max[1].floor() as i32 {
+                for z in min[2].floor() as i32..=max[2].floor() as i32 {
+                    // outside of the world is open, so the player can fall off the edge
+                    if context.world.get([x, y, z]).is_some_and(|b| context.blocks.is_solid(b)) {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+impl CameraController for WalkController {
+    fn name(&self) -> &'static str {
+        "walk"
+    }
+
+    fn activate(&mut self, _camera: &mut Camera, _context: &ControllerContext) {
+        self.velocity = [0.0; 3];
+        self.on_ground = false;
+    }
+
+    fn update(&mut self, camera: &mut Camera, context: &ControllerContext, t: f32) {
+        let input = context.input;
+        let mut speed = WALK_SPEED;
+        if input.is_held(self.settings.move_faster) {
+            speed *= 1.5;
+        }
+
+        // walking ignores pitch, looking down doesn't slow you down
+        let (sin, cos) = camera.yaw().sin_cos();
+        let heading = [sin, 0.0, cos];
+        let side = [cos, 0.0, -sin];
+        let (forward, sideways) = self.settings.movement(input);
+        let length = (forward * forward + sideways * sideways).sqrt().max(1.0);
+        self.velocity[0] = (heading[0] * forward + side[0] * sideways) / length * speed;
+        self.velocity[2] = (heading[2] * forward + side[2] * sideways) / length * speed;
+
+        if self.on_ground && input.is_held(self.settings.fly_up) {
+            self.velocity[1] = JUMP_SPEED;
+        }
+        self.velocity[1] = (self.velocity[1] - GRAVITY * t).max(-TERMINAL_VELOCITY);
+
+        // move one axis at a time, stopping against whatever is in the way
+        let mut feet = vec3_sub(camera.simulated_position(), [0.0, EYE_HEIGHT, 0.0]);
+        self.on_ground = false;
+        for axis in [1, 0, 2] {
+            let moved = self.velocity[axis] * t;
+            let mut next = feet;
+            next[axis] += moved;
+            if !Self::collides(next, context) {
+                feet = next;
+                continue;
+            }
+            // snap flush against the block that was hit
+            let (low, high) = if axis == 1 { (0.0, PLAYER_HEIGHT) } else { (-PLAYER_HALF_WIDTH, PLAYER_HALF_WIDTH) };
+            if moved > 0.0 {
+                next[axis] = (feet[axis] + moved + high).floor() - high - SKIN;
+            } else {
+                next[axis] = (feet[axis] + moved + low).floor() + 1.0 - low + SKIN;
+            }
+            if !Self::collides(next, context) {
+                feet = next;
+            }
+            if axis == 1 && moved < 0.0 {
+                self.on_ground = true;
+            }
+            self.velocity[axis] = 0.0;
+        }
+        camera.move_to(vec3_add(feet, [0.0, EYE_HEIGHT, 0.0]));
+    }
+}
+
+const MIN_ORBIT_DISTANCE: f32 = 2.0;
+const MAX_ORBIT_DISTANCE: f32 = 256.0;
+// how far ahead the orbit target is put when the camera isn't looking at a block
+const DEFAULT_ORBIT_DISTANCE: f32 = 24.0;
+
+/// Circles around a target point, turning with the mouse. Moving forward
+/// and back zooms in and out, and the other movement actions pan the target.
+#[derive(Default)]
+pub struct OrbitController {
+    pub settings: FirstPersonSettings,
+    target: Vector3<f32>,
+    distance: f32,
+}
+
+impl OrbitController {
+    fn place_camera(&self, camera: &mut Camera) {
+        camera.move_to(vec3_sub(self.target, vec3_scale(camera.forward, self.distance)));
+    }
+}
+
+impl CameraController for OrbitController {
+    fn name(&self) -> &'static str {
+        "orbit"
+    }
+
+    // orbits whatever the camera was looking at
+    fn activate(&mut self, camera: &mut Camera, context: &ControllerContext) {
+        let position = camera.simulated_position();
+        let hit = context.world.raycast(context.blocks, position, camera.forward, MAX_ORBIT_DISTANCE);
+        self.target = match hit {
+            Some((block, _)) => [block[0] as f32 + 0.5, block[1] as f32 + 0.5, block[2] as f32 + 0.5],
+            None => vec3_add(position, vec3_scale(camera.forward, DEFAULT_ORBIT_DISTANCE)),
+        };
+        self.distance = vec3_len(vec3_sub(self.target, position)).clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+    }
+
+    fn look(&mut self, camera: &mut Camera, turn: [f32; 2]) {
+        camera.turn(turn[0], turn[1]);
+        self.place_camera(camera);
+    }
+
+    fn update(&mut self, camera: &mut Camera, context: &ControllerContext, t: f32) {
+        let input = context.input;
+        let (zoom, sideways) = self.settings.movement(input);
+        // zooming is relative so it's as quick close up as far away
+        self.distance = (self.distance * (-zoom * 2.0 * t).exp()).clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+
+        let pan = self.distance * t;
+        let up = input.value(self.settings.fly_up) - input.value(self.settings.fly_down);
+        self.target = vec3_add(self.target, vec3_scale(camera.right, sideways * pan));
+        self.target = vec3_add(self.target, vec3_scale([0.0, 1.0, 0.0], up * pan));
+        self.place_camera(camera);
+    }
+}
+
+/// Flies along a closed Catmull-Rom path, looking the way it's going.
+pub struct SplineController {
+    points: Vec<Vector3<f32>>,
+    // path segments travelled per second
+    speed: f32,
+    // how far along the path it is, in segments
+    progress: f32,
+}
+
+impl SplineController {
+    pub fn new(points: Vec<Vector3<f32>>, speed: f32) -> Self {
+        Self { points, speed, progress: 0.0 }
+    }
+
+    // a loop of `count` points around a circle, bobbing up and down
+    pub fn circle(center: Vector3<f32>, radius: f32, count: usize) -> Self {
+        let points = (0..count)
+            .map(|i| {
+                let angle = i as f32 / count as f32 * 2.0 * PI;
+                let height = if i % 2 == 0 { 4.0 } else { -4.0 };
+                [center[0] + radius * angle.cos(), center[1] + height, center[2] + radius * angle.sin()]
+            })
+            .collect();
+        Self::new(points, 0.25)
+    }
+
+    fn place_camera(&self, camera: &mut Camera) {
+        let (position, tangent) = spline::sample_loop(&self.points, self.progress);
+        camera.move_to(position);
+        let horizontal = (tangent[0] * tangent[0] + tangent[2] * tangent[2]).sqrt();
+        camera.set_orientation(tangent[0].atan2(tangent[2]), tangent[1].atan2(horizontal));
+    }
+}
+
+impl CameraController for SplineController {
+    fn name(&self) -> &'static str {
+        "spline"
+    }
+
+    // starts the path over and jumps straight to its beginning
+    fn activate(&mut self, camera: &mut Camera, _context: &ControllerContext) {
+        self.progress = 0.0;
+        if !self.points.is_empty() {
+            self.place_camera(camera);
+        }
+    }
+
+    // the path decides where to look
+    fn look(&mut self, _camera: &mut Camera, _turn: [f32; 2]) {}
+
+    fn update(&mut self, camera: &mut Camera, _context: &ControllerContext, t: f32) {
+        if self.points.is_empty() {
+            return;
+        }
+        self.progress += self.speed * t;
+        self.place_camera(camera);
+    }
+}
@@ -1,5 +1,7 @@
 use glium::implement_vertex;
 
+use crate::visibility::Face;
+
 #[derive(Clone, Copy)]
 pub struct Vertex {
     position: [f32; 4],
@@ -9,8 +11,53 @@ pub struct Vertex {
 implement_vertex!(Vertex, position, tex_coords);
 
 impl Vertex {
-    pub fn transform(&mut self, mat: [[f32; 4]; 4 ]) {
-        self.position = vecmath::col_mat4_transform(mat, self.position);
+    // which corner of the unit cube this vertex sits on
+    pub fn corner(&self) -> [u8; 3] {
+        [self.position[0] as u8, self.position[1] as u8, self.position[2] as u8]
+    }
+
+    pub fn uv_corner(&self) -> [u8; 2] {
+        [self.tex_coords[0] as u8, self.tex_coords[1] as u8]
+    }
+}
+
+/// Chunk mesh vertex packed into 8 bytes, unpacked again in `shaders/vertex.glsl`.
+///
+/// `data[0]`: bits 0-29 chunk local x, y, z in sixteenths of a voxel (10 bits each),
+/// bits 30-31 ambient occlusion.
+/// `data[1]`: bits 0-9 u, v in sixteenths (5 bits each), bits 10-12 face,
+/// bits 13-14 log2 of the LOD scale, bits 15-18 light level, bits 19-31 texture layer.
+#[derive(Clone, Copy)]
+pub struct PackedVertex {
+    data: [u32; 2],
+}
+
+implement_vertex!(PackedVertex, data);
+
+impl PackedVertex {
+    pub fn pack(position: [u16; 3], face: Face, uv: [u8; 2], scale_log2: u8, ao: u8, layer: u16, light: u8) -> Self {
+        let low = position[0] as u32 & 1023
+            | (position[1] as u32 & 1023) << 10
+            | (position[2] as u32 & 1023) << 20
+            | (ao as u32 & 3) << 30;
+        let high = uv[0] as u32 & 31
+            | (uv[1] as u32 & 31) << 5
+            | (face as u32 & 7) << 10
+            | (scale_log2 as u32 & 3) << 13
+            | (light as u32 & 15) << 15
+            | (layer as u32 & 8191) << 19;
+        Self { data: [low, high] }
+    }
+}
+
+pub fn face_vertices(face: Face) -> [Vertex; 4] {
+    match face {
+        Face::East => EAST,
+        Face::West => WEST,
+        Face::Top => TOP,
+        Face::Bottom => BOTTOM,
+        Face::North => NORTH,
+        Face::South => SOUTH,
     }
 }
 
@@ -0,0 +1,199 @@
+use std::f32::consts::PI;
+
+use crate::input::{Action, Input};
+use crate::sky::SkyColors;
+
+// real seconds in a full day at normal speed
+pub const DAY_LENGTH: f32 = 1200.0;
+
+// times of day as fractions of a day
+pub const SUNRISE: f32 = 0.25;
+pub const NOON: f32 = 0.5;
+
+const DAY_HORIZON: [f32; 3] = [0.75, 0.85, 0.95];
+const DAY_ZENITH: [f32; 3] = [0.25, 0.5, 0.9];
+const NIGHT_HORIZON: [f32; 3] = [0.05, 0.06, 0.12];
+const NIGHT_ZENITH: [f32; 3] = [0.01, 0.01, 0.05];
+const SUNSET_HORIZON: [f32; 3] = [0.95, 0.55, 0.3];
+
+const NOON_LIGHT: [f32; 3] = [1.0, 0.98, 0.92];
+const SUNSET_LIGHT: [f32; 3] = [1.0, 0.6, 0.35];
+const MOON_LIGHT: [f32; 3] = [0.5, 0.55, 0.8];
+
+// sky light at night, as a fraction of the light at noon
+const NIGHT_LIGHT: f32 = 0.15;
+
+/// Time of day in the world, which keeps going while the game runs.
+/// PauseTime pauses it, SlowDownTime and SpeedUpTime halve and double its
+/// speed, and SkipToNoon skips to noon.
+pub struct WorldTime {
+    // fraction of the day, 0 is midnight and 0.5 is noon
+    time: f32,
+    speed: f32,
+    paused: bool,
+}
+
+impl WorldTime {
+    pub fn new(time: f32) -> Self {
+        Self {
+            time: time.rem_euclid(1.0),
+            speed: 1.0,
+            paused: false,
+        }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time.rem_euclid(1.0);
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    // `dt` in seconds
+    pub fn advance(&mut self, dt: f32) {
+        if !self.paused {
+            self.set_time(self.time + dt * self.speed / DAY_LENGTH);
+        }
+    }
+
+    pub fn update(&mut self, input: &Input) {
+        if input.is_pressed(Action::PauseTime) {
+            self.set_paused(!self.paused);
+        }
+        if input.is_pressed(Action::SlowDownTime) {
+            self.set_speed(self.speed / 2.0);
+        }
+        if input.is_pressed(Action::SpeedUpTime) {
+            self.set_speed(self.speed * 2.0);
+        }
+        if input.is_pressed(Action::SkipToNoon) {
+            self.set_time(NOON);
+        }
+    }
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// normalised direction towards the sun, it rises in the east (+x) and sets
+// in the west, tilted a little south so it isn't straight overhead at noon
+pub fn sun_direction(time: f32) -> [f32; 3] {
+    let angle = (time - SUNRISE) * 2.0 * PI;
+    let direction = [angle.cos(), angle.sin(), 0.3];
+    let length = (direction[0] * direction[0] + direction[1] * direction[1] + direction[2] * direction[2]).sqrt();
+    [direction[0] / length, direction[1] / length, direction[2] / length]
+}
+
+// 0 at night up to 1 once the sun is clear of the horizon
+fn daylight(time: f32) -> f32 {
+    smoothstep(-0.1, 0.2, sun_direction(time)[1])
+}
+
+// strongest while the sun is right on the horizon
+fn twilight(time: f32) -> f32 {
+    let height = sun_direction(time)[1] / 0.15;
+    (-height * height).exp()
+}
+
+// colour of the light from the sun, or the moon at night
+pub fn sun_color(time: f32) -> [f32; 3] {
+    let sun = mix(SUNSET_LIGHT, NOON_LIGHT, smoothstep(0.0, 0.3, sun_direction(time)[1]));
+    mix(MOON_LIGHT, sun, daylight(time))
+}
+
+// brightness of the sky light, 1 at noon
+pub fn sky_light(time: f32) -> f32 {
+    NIGHT_LIGHT + (1.0 - NIGHT_LIGHT) * daylight(time)
+}
+
+pub fn sky_colors(time: f32) -> SkyColors {
+    let daylight = daylight(time);
+    let horizon = mix(NIGHT_HORIZON, DAY_HORIZON, daylight);
+    SkyColors {
+        horizon: mix(horizon, SUNSET_HORIZON, twilight(time) * 0.7),
+        zenith: mix(NIGHT_ZENITH, DAY_ZENITH, daylight),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f32; 3], b: [f32; 3], epsilon: f32) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < epsilon, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn noon() {
+        assert!(sun_direction(NOON)[1] > 0.9);
+        assert!((sky_light(NOON) - 1.0).abs() < 1e-4);
+        assert_close(sun_color(NOON), NOON_LIGHT, 1e-4);
+        let colors = sky_colors(NOON);
+        assert_close(colors.horizon, DAY_HORIZON, 1e-4);
+        assert_close(colors.zenith, DAY_ZENITH, 1e-4);
+    }
+
+    #[test]
+    fn midnight() {
+        assert!(sun_direction(0.0)[1] < -0.9);
+        assert!((sky_light(0.0) - NIGHT_LIGHT).abs() < 1e-4);
+        assert_close(sun_color(0.0), MOON_LIGHT, 1e-4);
+        let colors = sky_colors(0.0);
+        assert_close(colors.horizon, NIGHT_HORIZON, 1e-4);
+        assert_close(colors.zenith, NIGHT_ZENITH, 1e-4);
+    }
+
+    #[test]
+    fn continuous_over_the_day() {
+        // includes the wrap from just before midnight back to 0
+        let steps = 10000;
+        for i in 0..=steps {
+            let a = i as f32 / steps as f32;
+            let b = ((i + 1) as f32 / steps as f32).rem_euclid(1.0);
+            assert_close(sun_direction(a), sun_direction(b), 0.001);
+            assert_close(sun_color(a), sun_color(b), 0.01);
+            assert!((sky_light(a) - sky_light(b)).abs() < 0.01);
+            assert_close(sky_colors(a).horizon, sky_colors(b).horizon, 0.01);
+            assert_close(sky_colors(a).zenith, sky_colors(b).zenith, 0.01);
+        }
+    }
+
+    #[test]
+    fn advance_wraps() {
+        let mut time = WorldTime::new(0.9);
+        time.advance(0.2 * DAY_LENGTH);
+        assert!((time.time() - 0.1).abs() < 1e-4);
+        assert!(WorldTime::new(-0.25).time() == 0.75);
+        time.set_speed(2.0);
+        time.advance(0.25 * DAY_LENGTH);
+        assert!((time.time() - 0.6).abs() < 1e-4);
+    }
+
+    #[test]
+    fn advance_paused() {
+        let mut time = WorldTime::new(NOON);
+        time.set_paused(true);
+        time.advance(DAY_LENGTH / 3.0);
+        assert_eq!(time.time(), NOON);
+        time.set_paused(false);
+        time.advance(DAY_LENGTH / 4.0);
+        assert!((time.time() - 0.75).abs() < 1e-4);
+    }
+}
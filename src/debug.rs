@@ -1,20 +1,27 @@
 use glium::glutin::event_loop::ControlFlow;
 
+use crate::input::{Action, Input};
+use crate::sky::FogMode;
+
 extern crate glium;
 
 pub struct Debug {
-    keys: Vec<glium::glutin::event::VirtualKeyCode>,
+    // the world is drawn as lines while Wireframe is held
+    wireframe: bool,
+    // ToggleFog switches between linear and exponential fog
+    fog_mode: FogMode,
 }
 
 impl Debug {
     pub fn new() -> Self {
         Self {
-            keys: Vec::new(),
+            wireframe: false,
+            fog_mode: FogMode::Linear,
         }
     }
 
     pub fn get_draw_state(&self) -> glium::draw_parameters::PolygonMode {
-        if self.keys.contains(&glium::glutin::event::VirtualKeyCode::T) {
+        if self.wireframe {
             glium::draw_parameters::PolygonMode::Line
         } 
         else {
@@ -22,32 +29,17 @@ impl Debug {
         }
     }
 
-    pub fn parse_input(&mut self, e: &glium::glutin::event::DeviceEvent, control_flow: &mut ControlFlow) {
-        match e {
-            glium::glutin::event::DeviceEvent::Key(glium::glutin::event::KeyboardInput { state, virtual_keycode, .. }) => {
-                let virtual_keycode = virtual_keycode.unwrap();
-                match state {
-                    glium::glutin::event::ElementState::Pressed => {
-                        if !self.keys.contains(&virtual_keycode) {
-                            self.keys.push(virtual_keycode);
-                        }
-                    }
-                    glium::glutin::event::ElementState::Released => {
-                        if self.keys.contains(&virtual_keycode) {
-                            let i = self.keys.iter().position(|&r| r == virtual_keycode).unwrap();
-                            self.keys.remove(i);
-                        }
-                    }
-                }
-                match virtual_keycode {
-                    glium::glutin::event::VirtualKeyCode::Escape => {
-                            *control_flow = ControlFlow::Exit;
-                            return;
-                    },
-                    _ => {},
-                }
-            },
-            _ => (),
+    pub fn get_fog_mode(&self) -> FogMode {
+        self.fog_mode
+    }
+
+    pub fn update(&mut self, input: &Input, control_flow: &mut ControlFlow) {
+        self.wireframe = input.is_held(Action::Wireframe);
+        if input.is_pressed(Action::ToggleFog) {
+            self.fog_mode = self.fog_mode.toggled();
+        }
+        if input.is_pressed(Action::Quit) {
+            *control_flow = ControlFlow::Exit;
         }
     }
 }
@@ -0,0 +1,203 @@
+use crate::block::{self, BlockRegistry, Fluid, MAX_FLUID_LEVEL};
+use crate::tick::{offset, VoxelGrid};
+use crate::visibility::Face;
+
+const HORIZONTAL: [Face; 4] = [Face::East, Face::West, Face::North, Face::South];
+
+/// What a cell should become as fluids flow into and out of it, `None` for
+/// cells fluids can't go into.
+pub fn next_block(grid: &impl VoxelGrid, blocks: &BlockRegistry, position: [i32; 3]) -> Option<i8> {
+    let current = grid.get(position)?;
+    let fluid = match blocks.fluid(current) {
+        // sources never change
+        Some(fluid) if fluid.level == 0 => return None,
+        Some(fluid) => Some(*fluid),
+        None if current == block::AIR => None,
+        None => return None,
+    };
+
+    // air takes whichever neighbouring fluid comes first in a fixed order
+    let candidates: Vec<Fluid> = match fluid {
+        Some(fluid) => vec![fluid],
+        None => {
+            let mut found: Vec<Fluid> = [Face::Top, Face::East, Face::West, Face::North, Face::South]
+                .iter()
+                .filter_map(|&face| grid.get(offset(position, face)).and_then(|b| blocks.fluid(b)).copied())
+                .collect();
+            found.sort_by_key(|f| f.source);
+            found.dedup_by_key(|f| f.source);
+            found
+        },
+    };
+
+    for fluid in candidates {
+        if let Some(level) = fluid_level(grid, blocks, position, &fluid) {
+            return Some(blocks.fluid_block(fluid.source, level));
+        }
+    }
+    Some(block::AIR)
+}
+
+fn is_fluid(blocks: &BlockRegistry, block: Option<i8>, source: i8) -> Option<u8> {
+    block.and_then(|b| blocks.fluid(b)).filter(|f| f.source == source).map(|f| f.level)
+}
+
+// whether fluid resting on `below` has something to spread out over
+fn is_floor(blocks: &BlockRegistry, below: Option<i8>, source: i8) -> bool {
+    match below {
+        None => true,
+        Some(block::AIR) => false,
+        Some(block) => is_fluid(blocks, Some(block), source).is_none_or(|level| level == 0),
+    }
+}
+
+// the level `fluid` would have at `position`, `None` when nothing feeds it
+fn fluid_level(grid: &impl VoxelGrid, blocks: &BlockRegistry, position: [i32; 3], fluid: &Fluid) -> Option<u8> {
+    let source = fluid.source;
+    let below = grid.get(offset(position, Face::Bottom));
+
+    // a gap between two sources fills in, as long as it has a floor
+    if fluid.definition.renewable && is_floor(blocks, below, source) {
+        let sources = HORIZONTAL
+            .iter()
+            .filter(|&&face| is_fluid(blocks, grid.get(offset(position, face)), source) == Some(0))
+            .count();
+        if sources >= 2 {
+            return Some(0);
+        }
+    }
+
+    // falling fluid is always at full strength
+    if is_fluid(blocks, grid.get(offset(position, Face::Top)), source).is_some() {
+        return Some(1);
+    }
+
+    // otherwise it's fed from the side by fluid that can't fall any further
+    HORIZONTAL
+        .iter()
+        .filter_map(|&face| {
+            let neighbour = offset(position, face);
+            let level = is_fluid(blocks, grid.get(neighbour), source)?;
+            let floor = is_floor(blocks, grid.get(offset(neighbour, Face::Bottom)), source);
+            if floor { Some(level) } else { None }
+        })
+        .min()
+        .map(|level| level + fluid.definition.level_step())
+        .filter(|&level| level <= MAX_FLUID_LEVEL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tick::tests::{registry, Grid};
+
+    // runs `next_block` over the whole grid until nothing changes
+    fn settle(grid: &mut Grid, blocks: &BlockRegistry) {
+        for _ in 0..64 {
+            let mut changes = Vec::new();
+            for x in 0..8 {
+                for y in 0..8 {
+                    for z in 0..8 {
+                        let current = grid.get([x, y, z]);
+                        match next_block(grid, blocks, [x, y, z]) {
+                            Some(next) if Some(next) != current => changes.push(([x, y, z], next)),
+                            _ => {},
+                        }
+                    }
+                }
+            }
+            if changes.is_empty() {
+                return;
+            }
+            for (position, block) in changes {
+                grid.fill(position, position, block);
+            }
+        }
+        panic!("fluid never settled");
+    }
+
+    fn level(grid: &Grid, blocks: &BlockRegistry, position: [i32; 3]) -> Option<u8> {
+        blocks.fluid(grid.get(position)?).map(|f| f.level)
+    }
+
+    fn floor(blocks: &BlockRegistry) -> Grid {
+        let mut grid = Grid::new(8);
+        grid.fill([0, 0, 0], [7, 0, 7], blocks.id("stone").unwrap());
+        grid
+    }
+
+    #[test]
+    fn sources_stay() {
+        let blocks = registry();
+        let water = blocks.id("water").unwrap();
+        let mut grid = floor(&blocks);
+        grid.fill([3, 1, 3], [3, 1, 3], water);
+        assert_eq!(next_block(&grid, &blocks, [3, 1, 3]), None);
+        // neither does anything solid
+        assert_eq!(next_block(&grid, &blocks, [3, 0, 3]), None);
+    }
+
+    #[test]
+    fn levels_decay_while_spreading() {
+        let blocks = registry();
+        for (name, step) in [("water", 1), ("lava", 2)] {
+            let source = blocks.id(name).unwrap();
+            let mut grid = floor(&blocks);
+            grid.fill([0, 1, 0], [0, 1, 0], source);
+            settle(&mut grid, &blocks);
+            for x in 0..8 {
+                let expected = Some(x as u8 * step).filter(|&level| level <= MAX_FLUID_LEVEL);
+                assert_eq!(level(&grid, &blocks, [x, 1, 0]), expected, "{} at {}", name, x);
+            }
+            // spreads in a diamond, diagonals are further away
+            assert_eq!(level(&grid, &blocks, [1, 1, 1]), Some(2 * step));
+            assert_eq!(level(&grid, &blocks, [0, 2, 0]), None);
+        }
+    }
+
+    #[test]
+    fn dries_up_without_a_source() {
+        let blocks = registry();
+        let water = blocks.id("water").unwrap();
+        let mut grid = floor(&blocks);
+        grid.fill([3, 1, 3], [3, 1, 3], blocks.fluid_block(water, 2));
+        assert_eq!(next_block(&grid, &blocks, [3, 1, 3]), Some(block::AIR));
+    }
+
+    #[test]
+    fn falls_at_full_strength() {
+        let blocks = registry();
+        let water = blocks.id("water").unwrap();
+        let mut grid = floor(&blocks);
+        grid.fill([3, 6, 3], [3, 6, 3], water);
+        settle(&mut grid, &blocks);
+        for y in 1..6 {
+            assert_eq!(level(&grid, &blocks, [3, y, 3]), Some(1), "at height {}", y);
+        }
+        // falling water doesn't spread sideways in mid-air
+        assert_eq!(level(&grid, &blocks, [4, 5, 3]), None);
+        assert_eq!(level(&grid, &blocks, [4, 6, 3]), None);
+        // but does once it lands
+        assert_eq!(level(&grid, &blocks, [4, 1, 3]), Some(2));
+    }
+
+    #[test]
+    fn renewable_sources() {
+        let blocks = registry();
+        for (name, renewable) in [("water", true), ("lava", false)] {
+            let source = blocks.id(name).unwrap();
+            let mut grid = floor(&blocks);
+            grid.fill([2, 1, 3], [2, 1, 3], source);
+            grid.fill([4, 1, 3], [4, 1, 3], source);
+            let next = next_block(&grid, &blocks, [3, 1, 3]).unwrap();
+            assert_eq!(blocks.fluid(next).unwrap().level == 0, renewable, "{}", name);
+        }
+
+        // but not over a drop
+        let water = blocks.id("water").unwrap();
+        let mut grid = floor(&blocks);
+        grid.fill([2, 2, 3], [2, 2, 3], water);
+        grid.fill([4, 2, 3], [4, 2, 3], water);
+        assert_eq!(next_block(&grid, &blocks, [3, 2, 3]), Some(block::AIR));
+    }
+}
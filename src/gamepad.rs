@@ -0,0 +1,211 @@
+use gilrs::{Axis, Gilrs, GilrsBuilder};
+
+use crate::input::{AxisDirection, Button, Input};
+
+// every stick axis and button a gamepad can feed into the action map
+const AXES: [Axis; 4] = [Axis::LeftStickX, Axis::LeftStickY, Axis::RightStickX, Axis::RightStickY];
+const BUTTONS: [gilrs::Button; 19] = [
+    gilrs::Button::South,
+    gilrs::Button::East,
+    gilrs::Button::North,
+    gilrs::Button::West,
+    gilrs::Button::C,
+    gilrs::Button::Z,
+    gilrs::Button::LeftTrigger,
+    gilrs::Button::LeftTrigger2,
+    gilrs::Button::RightTrigger,
+    gilrs::Button::RightTrigger2,
+    gilrs::Button::Select,
+    gilrs::Button::Start,
+    gilrs::Button::Mode,
+    gilrs::Button::LeftThumb,
+    gilrs::Button::RightThumb,
+    gilrs::Button::DPadUp,
+    gilrs::Button::DPadDown,
+    gilrs::Button::DPadLeft,
+    gilrs::Button::DPadRight,
+];
+
+/// How raw stick and trigger values are turned into action values.
+#[derive(Clone, Copy, Debug)]
+pub struct GamepadSettings {
+    // sticks read as centred below `inner` and as fully tilted past `outer`
+    pub move_deadzone: (f32, f32),
+    pub look_deadzone: (f32, f32),
+    pub trigger_deadzone: (f32, f32),
+    // exponent of the look response curve, above 1 gives finer control near
+    // the centre and faster turning at full tilt
+    pub look_curve: f32,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            move_deadzone: (0.15, 0.95),
+            look_deadzone: (0.1, 0.95),
+            trigger_deadzone: (0.1, 0.9),
+            look_curve: 2.0,
+        }
+    }
+}
+
+/// Rescales `value` so everything up to `inner` is 0, everything past
+/// `outer` is 1, and it ramps evenly in between.
+pub fn deadzone(value: f32, inner: f32, outer: f32) -> f32 {
+    if outer <= inner {
+        return if value.abs() > inner { value.signum() } else { 0.0 };
+    }
+    let magnitude = ((value.abs() - inner) / (outer - inner)).clamp(0.0, 1.0);
+    magnitude * value.signum()
+}
+
+/// A radial deadzone for a stick, applied to how far it's tilted rather than
+/// each axis on its own so diagonals don't snap to the axes.
+pub fn stick_deadzone(stick: [f32; 2], inner: f32, outer: f32) -> [f32; 2] {
+    let length = (stick[0] * stick[0] + stick[1] * stick[1]).sqrt();
+    if length == 0.0 {
+        return [0.0, 0.0];
+    }
+    let scaled = deadzone(length, inner, outer);
+    [stick[0] / length * scaled, stick[1] / length * scaled]
+}
+
+/// Raises how far a stick is tilted to `exponent`, keeping its direction.
+pub fn response_curve(stick: [f32; 2], exponent: f32) -> [f32; 2] {
+    let length = (stick[0] * stick[0] + stick[1] * stick[1]).sqrt();
+    if length == 0.0 {
+        return [0.0, 0.0];
+    }
+    let curved = length.min(1.0).powf(exponent);
+    [stick[0] / length * curved, stick[1] / length * curved]
+}
+
+/// Connected gamepads, polled every frame into the same `Input` as the
+/// keyboard and mouse.
+pub struct Gamepads {
+    // `None` when gamepads aren't supported here, the game still runs without them
+    gilrs: Option<Gilrs>,
+    pub settings: GamepadSettings,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        // the default filters add a deadzone of their own
+        let gilrs = match GilrsBuilder::new().with_default_filters(false).build() {
+            Ok(gilrs) => Some(gilrs),
+            Err(error) => {
+                eprintln!("gamepads are unavailable: {}", error);
+                None
+            },
+        };
+        Self { gilrs, settings: GamepadSettings::default() }
+    }
+
+    pub fn poll(&mut self, input: &mut Input) {
+        let gilrs = match self.gilrs.as_mut() {
+            Some(gilrs) => gilrs,
+            None => return,
+        };
+        // events only need draining, the state of every gamepad is read below
+        while gilrs.next_event().is_some() {}
+
+        // with more than one gamepad whichever is pushed furthest wins
+        let mut sticks = [[0.0f32; 2]; 2];
+        let mut buttons = [0.0f32; BUTTONS.len()];
+        for (_, gamepad) in gilrs.gamepads() {
+            let value = |axis| gamepad.axis_data(axis).map_or(0.0, |data| data.value());
+            let left = stick_deadzone([value(AXES[0]), value(AXES[1])], self.settings.move_deadzone.0, self.settings.move_deadzone.1);
+            let right = stick_deadzone([value(AXES[2]), value(AXES[3])], self.settings.look_deadzone.0, self.settings.look_deadzone.1);
+            let right = response_curve(right, self.settings.look_curve);
+            for (stick, new) in sticks.iter_mut().zip([left, right]) {
+                if new[0].hypot(new[1]) > stick[0].hypot(stick[1]) {
+                    *stick = new;
+                }
+            }
+            for (pressed, &button) in buttons.iter_mut().zip(BUTTONS.iter()) {
+                let value = gamepad.button_data(button).map_or(0.0, |data| data.value());
+                let (inner, outer) = self.settings.trigger_deadzone;
+                *pressed = pressed.max(deadzone(value, inner, outer));
+            }
+        }
+
+        for (i, &axis) in AXES.iter().enumerate() {
+            let value = sticks[i / 2][i % 2];
+            input.set_analog(Button::PadAxis(axis, AxisDirection::Positive), value.max(0.0));
+            input.set_analog(Button::PadAxis(axis, AxisDirection::Negative), (-value).max(0.0));
+        }
+        for (&button, &value) in BUTTONS.iter().zip(buttons.iter()) {
+            input.set_analog(Button::Pad(button), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn length(stick: [f32; 2]) -> f32 {
+        (stick[0] * stick[0] + stick[1] * stick[1]).sqrt()
+    }
+
+    #[test]
+    fn inside_deadzone() {
+        for value in [0.0, 0.05, -0.1, 0.15, -0.15] {
+            assert_eq!(deadzone(value, 0.15, 0.95), 0.0);
+        }
+        assert_eq!(stick_deadzone([0.1, -0.1], 0.15, 0.95), [0.0, 0.0]);
+        assert_eq!(stick_deadzone([0.0, 0.0], 0.15, 0.95), [0.0, 0.0]);
+    }
+
+    #[test]
+    fn rescaled_to_full() {
+        assert_eq!(deadzone(0.95, 0.15, 0.95), 1.0);
+        assert_eq!(deadzone(1.0, 0.15, 0.95), 1.0);
+        assert_eq!(deadzone(-1.0, 0.15, 0.95), -1.0);
+        assert!((deadzone(0.55, 0.15, 0.95) - 0.5).abs() < 1e-6);
+        // gamepads often report a little past 1 on the diagonals
+        let stick = stick_deadzone([0.8, 0.8], 0.15, 0.95);
+        assert!((length(stick) - 1.0).abs() < 1e-6);
+        // an empty ramp acts as a plain threshold
+        assert_eq!(deadzone(0.3, 0.2, 0.2), 1.0);
+        assert_eq!(deadzone(-0.1, 0.2, 0.2), 0.0);
+    }
+
+    #[test]
+    fn radial_deadzone_keeps_direction() {
+        for stick in [[0.3, 0.4], [-0.9, 0.2], [0.01, -0.7], [0.5, 0.5]] {
+            let scaled = stick_deadzone(stick, 0.1, 0.95);
+            let cross = stick[0] * scaled[1] - stick[1] * scaled[0];
+            let dot = stick[0] * scaled[0] + stick[1] * scaled[1];
+            assert!(cross.abs() < 1e-6 && dot > 0.0, "{:?} became {:?}", stick, scaled);
+        }
+        // a diagonal past the inner deadzone isn't cut off even though each
+        // axis on its own is inside it
+        let scaled = stick_deadzone([0.12, 0.12], 0.15, 0.95);
+        assert!(scaled[0] > 0.0 && scaled[1] > 0.0);
+    }
+
+    #[test]
+    fn curve_endpoints() {
+        for exponent in [0.5, 1.0, 2.0, 3.0] {
+            assert_eq!(response_curve([0.0, 0.0], exponent), [0.0, 0.0]);
+            assert!((length(response_curve([0.6, 0.8], exponent)) - 1.0).abs() < 1e-6);
+            assert!((length(response_curve([0.0, -1.0], exponent)) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn curve_monotonic() {
+        for exponent in [0.5, 1.0, 2.0, 3.0] {
+            let mut last = 0.0;
+            for i in 1..=100 {
+                let tilt = i as f32 / 100.0;
+                let curved = length(response_curve([tilt * 0.6, tilt * 0.8], exponent));
+                assert!(curved > last, "curve {} dips at {}", exponent, tilt);
+                last = curved;
+            }
+        }
+        // finer near the centre above an exponent of 1
+        assert!(length(response_curve([0.5, 0.0], 2.0)) < 0.5);
+    }
+}
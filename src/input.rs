@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet};
+
+use glium::glutin::event::{DeviceEvent, ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+use serde::{Deserialize, Serialize};
+
+/// Everything the player can do, bound to keys and buttons in `bindings.ron`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    StrafeLeft,
+    StrafeRight,
+    FlyUp,
+    FlyDown,
+    MoveFaster,
+    // turning with a stick, the mouse turns the camera directly
+    LookLeft,
+    LookRight,
+    LookUp,
+    LookDown,
+    // the block the camera is looking at
+    Break,
+    Place,
+    Quit,
+    // cycles through the camera controllers
+    SwitchCamera,
+    // goes between perspective, reverse-z and orthographic projections
+    SwitchProjection,
+    // change the camera's field of view
+    WidenView,
+    NarrowView,
+    // move the camera's near and far planes, the far one does nothing with reverse-z
+    NearPlaneCloser,
+    NearPlaneFurther,
+    FarPlaneCloser,
+    FarPlaneFurther,
+    // starts recording the camera's movement, pressed again it saves it
+    Record,
+    // plays the last recording back
+    PlayRecording,
+    // frees the mouse to leave the window, clicking back in grabs it again
+    ReleaseCursor,
+    GrabCursor,
+    // switches between windowed and the fullscreen mode from the settings
+    ToggleFullscreen,
+    // held to draw the world as lines
+    Wireframe,
+    ToggleFog,
+    PauseTime,
+    SlowDownTime,
+    SpeedUpTime,
+    SkipToNoon,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Button {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    // gamepad buttons, triggers are analog
+    Pad(gilrs::Button),
+    // one direction of a gamepad stick
+    PadAxis(gilrs::Axis, AxisDirection),
+}
+
+// analog buttons count as held once pushed this far
+const PRESS_THRESHOLD: f32 = 0.5;
+
+/// The buttons bound to each action, any of them triggers it.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(transparent)]
+pub struct Bindings(HashMap<Action, Vec<Button>>);
+
+impl Bindings {
+    pub fn from_ron(source: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(source)
+    }
+
+    // actions bound in `other` take its buttons instead
+    pub fn merge(&mut self, other: &Bindings) {
+        for (action, buttons) in other.0.iter() {
+            self.0.insert(*action, buttons.clone());
+        }
+    }
+
+    pub fn buttons(&self, action: Action) -> &[Button] {
+        self.0.get(&action).map_or(&[], |buttons| buttons.as_slice())
+    }
+}
+
+/// Which buttons are held, and which went down or up since the last frame.
+pub struct Input {
+    bindings: Bindings,
+    held: HashSet<Button>,
+    pressed: HashSet<Button>,
+    released: HashSet<Button>,
+    // raw mouse movement since the last frame
+    mouse_delta: (f64, f64),
+    // how far each analog button is pushed, from 0 to 1
+    analog: HashMap<Button, f32>,
+}
+
+impl Input {
+    pub fn new(bindings: Bindings) -> Self {
+        Self {
+            bindings,
+            held: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+            mouse_delta: (0.0, 0.0),
+            analog: HashMap::new(),
+        }
+    }
+
+    // raw mouse motion is unaccelerated and keeps going at the edge of the
+    // screen. keys only come from the window so typing elsewhere is ignored
+    pub fn parse_device_event(&mut self, e: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = e {
+            self.mouse_delta.0 += delta.0;
+            self.mouse_delta.1 += delta.1;
+        }
+    }
+
+    pub fn parse_window_event(&mut self, e: &WindowEvent) {
+        match e {
+            WindowEvent::KeyboardInput { input: KeyboardInput { state, virtual_keycode: Some(key), .. }, .. } => {
+                self.set(Button::Key(*key), *state);
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.set(Button::Mouse(*button), *state);
+            },
+            _ => {},
+        }
+    }
+
+    // the window doesn't hear about keys let go while it's unfocused, so
+    // everything held is released when it loses focus instead of sticking
+    pub fn release_all(&mut self) {
+        self.released.extend(self.held.drain());
+        self.analog.clear();
+    }
+
+    // gamepads report where their sticks and triggers are every frame
+    pub fn set_analog(&mut self, button: Button, value: f32) {
+        let held = value >= PRESS_THRESHOLD;
+        self.set(button, if held { ElementState::Pressed } else { ElementState::Released });
+        if value > 0.0 {
+            self.analog.insert(button, value);
+        } else {
+            self.analog.remove(&button);
+        }
+    }
+
+    fn set(&mut self, button: Button, state: ElementState) {
+        match state {
+            // held keys repeat, only the first press counts
+            ElementState::Pressed => {
+                if self.held.insert(button) {
+                    self.pressed.insert(button);
+                }
+            },
+            ElementState::Released => {
+                if self.held.remove(&button) {
+                    self.released.insert(button);
+                }
+            },
+        }
+    }
+
+    // call once a frame, after everything has read the input
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.mouse_delta = (0.0, 0.0);
+    }
+
+    pub fn mouse_delta(&self) -> (f64, f64) {
+        self.mouse_delta
+    }
+
+    // how strongly the action is held from 0 to 1, keys and buttons are
+    // either 0 or 1 while sticks and triggers go in between
+    pub fn value(&self, action: Action) -> f32 {
+        self.bindings
+            .buttons(action)
+            .iter()
+            .map(|b| match self.analog.get(b) {
+                Some(&value) => value,
+                None if self.held.contains(b) => 1.0,
+                None => 0.0,
+            })
+            .fold(0.0, f32::max)
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        self.bindings.buttons(action).iter().any(|b| self.held.contains(b))
+    }
+
+    // whether the action started this frame
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.bindings.buttons(action).iter().any(|b| self.pressed.contains(b))
+    }
+
+    // whether the action stopped this frame
+    // TODO: remove once something reacts to releases
+    #[allow(dead_code)]
+    pub fn is_released(&self, action: Action) -> bool {
+        self.bindings.buttons(action).iter().any(|b| self.released.contains(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glium::glutin::event::{DeviceId, ModifiersState};
+
+    #[allow(deprecated)]
+    fn key(key: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::KeyboardInput {
+            device_id: unsafe { DeviceId::dummy() },
+            input: KeyboardInput { scancode: 0, state, virtual_keycode: Some(key), modifiers: ModifiersState::empty() },
+            is_synthetic: false,
+        }
+    }
+
+    fn input() -> Input {
+        Input::new(Bindings::from_ron("{ MoveForward: [Key(W)] }").unwrap())
+    }
+
+    #[test]
+    fn default_bindings() {
+        let source = std::fs::read_to_string("assets/bindings.ron").unwrap();
+        let bindings = Bindings::from_ron(&source).unwrap();
+        assert_eq!(bindings.buttons(Action::WidenView), &[Button::Key(VirtualKeyCode::Equals)]);
+        assert!(!bindings.buttons(Action::MoveForward).is_empty());
+    }
+
+    #[test]
+    fn keys_from_the_window() {
+        let mut input = input();
+        input.parse_window_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(input.is_pressed(Action::MoveForward));
+        assert!(input.is_held(Action::MoveForward));
+        input.end_frame();
+        // repeats don't press again
+        input.parse_window_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        assert!(!input.is_pressed(Action::MoveForward));
+        input.parse_window_event(&key(VirtualKeyCode::W, ElementState::Released));
+        assert!(input.is_released(Action::MoveForward));
+        assert!(!input.is_held(Action::MoveForward));
+    }
+
+    #[test]
+    fn losing_focus_releases_keys() {
+        let mut input = input();
+        input.parse_window_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+        input.set_analog(Button::PadAxis(gilrs::Axis::LeftStickY, AxisDirection::Positive), 0.8);
+        input.end_frame();
+        input.release_all();
+        assert!(!input.is_held(Action::MoveForward));
+        assert!(input.is_released(Action::MoveForward));
+        assert_eq!(input.value(Action::MoveForward), 0.0);
+        assert!(input.analog.is_empty());
+    }
+}
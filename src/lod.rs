@@ -0,0 +1,136 @@
+use crate::chunkmesh::{ChunkMesh, VoxelMap, CHUNK_SIZE};
+use crate::block::BlockRegistry;
+
+// number of detail levels per chunk, each halving the voxel resolution
+pub const LOD_LEVELS: usize = 4;
+
+// chunk distance from the camera at which each of the lower detail levels
+// starts being used, level 0 (full resolution) is used below the first
+pub const LOD_DISTANCES: [i32; LOD_LEVELS - 1] = [2, 4, 6];
+
+// size of the merged voxel blocks at a given level: 1, 2, 4, 8
+pub fn lod_scale(level: usize) -> usize {
+    1 << level
+}
+
+// pick a detail level from the chebyshev distance between two chunks
+pub fn select_level(camera_chunk: [i32; 3], chunk: [i32; 3]) -> usize {
+    let distance = (0..3)
+        .map(|i| (chunk[i] - camera_chunk[i]).abs())
+        .max()
+        .unwrap_or(0);
+    LOD_DISTANCES.iter().filter(|&&d| distance >= d).count()
+}
+
+/// Merges every `scale`³ block of voxels into one, which is solid when at
+/// least half of the voxels in it are. The result is still a full resolution
+/// map so it can be meshed with `ChunkMesh::generate_mesh_scaled`.
+pub fn downsample(voxels: &VoxelMap, scale: usize) -> VoxelMap {
+    let mut merged: VoxelMap = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+    let threshold = scale * scale * scale / 2;
+
+    for bx in (0..CHUNK_SIZE).step_by(scale) {
+        for by in (0..CHUNK_SIZE).step_by(scale) {
+            for bz in (0..CHUNK_SIZE).step_by(scale) {
+                let mut solid = 0;
+                let mut block = 0;
+                for plane in &voxels[bx..bx + scale] {
+                    for column in &plane[by..by + scale] {
+                        for &voxel in &column[bz..bz + scale] {
+                            if voxel != 0 {
+                                solid += 1;
+                                block = voxel;
+                            }
+                        }
+                    }
+                }
+                if solid < threshold {
+                    continue;
+                }
+                for plane in &mut merged[bx..bx + scale] {
+                    for column in &mut plane[by..by + scale] {
+                        for voxel in &mut column[bz..bz + scale] {
+                            *voxel = block;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    merged
+}
+
+// meshes for every level below full resolution
+pub fn generate_lod_meshes(voxels: &VoxelMap, blocks: &BlockRegistry) -> Vec<ChunkMesh> {
+    (1..LOD_LEVELS)
+        .map(|level| {
+            let scale = lod_scale(level);
+            // nothing stitches the levels together, a chunk next to one at a
+            // different level only hides the crack because border faces are
+            // always drawn. Where the downsampled surfaces don't line up the
+            // seam can still show as a step or a sliver of background
+            let mut mesh = ChunkMesh::from_voxels(downsample(voxels, scale));
+            mesh.generate_mesh_scaled(scale, blocks);
+            mesh
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a chunk with the first `count` voxels of the block at the origin filled
+    fn partly_filled(scale: usize, count: usize) -> Box<VoxelMap> {
+        let mut voxels: Box<VoxelMap> = Box::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+        for i in 0..count {
+            voxels[i / (scale * scale)][i / scale % scale][i % scale] = 3;
+        }
+        voxels
+    }
+
+    #[test]
+    fn uniform_chunk() {
+        let voxels: Box<VoxelMap> = Box::new([[[2; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+        for level in 1..LOD_LEVELS {
+            let merged = downsample(&voxels, lod_scale(level));
+            assert!(merged.iter().flatten().flatten().all(|&v| v == 2));
+        }
+        let empty: Box<VoxelMap> = Box::new([[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]);
+        let merged = downsample(&empty, 2);
+        assert!(merged.iter().flatten().flatten().all(|&v| v == 0));
+    }
+
+    #[test]
+    fn half_filled_threshold() {
+        for level in 1..LOD_LEVELS {
+            let scale = lod_scale(level);
+            let half = scale * scale * scale / 2;
+
+            let merged = downsample(&partly_filled(scale, half), scale);
+            let block = merged[..scale].iter().flat_map(|plane| &plane[..scale]);
+            assert!(block.flat_map(|column| &column[..scale]).all(|&v| v == 3), "scale {}", scale);
+            // only the block holding the voxels is filled
+            assert_eq!(merged[scale][0][0], 0);
+
+            let merged = downsample(&partly_filled(scale, half - 1), scale);
+            assert!(merged.iter().flatten().flatten().all(|&v| v == 0), "scale {}", scale);
+        }
+    }
+
+    #[test]
+    fn level_boundaries() {
+        let levels = [0, 0, 1, 1, 2, 2, 3, 3, 3];
+        for (distance, &level) in levels.iter().enumerate() {
+            let d = distance as i32;
+            assert_eq!(select_level([0, 0, 0], [d, 0, 0]), level, "distance {}", d);
+            assert_eq!(select_level([5, 5, 5], [5, 5 - d, 5]), level, "distance {}", d);
+            // chebyshev distance, the furthest axis decides
+            assert_eq!(select_level([0, 0, 0], [1, -1, d]), level, "distance {}", d);
+        }
+        for (i, &d) in LOD_DISTANCES.iter().enumerate() {
+            assert_eq!(select_level([0, 0, 0], [0, 0, d - 1]), i);
+            assert_eq!(select_level([0, 0, 0], [0, 0, d]), i + 1);
+        }
+    }
+}
@@ -1,7 +1,40 @@
+mod assets;
+mod benchmark;
 mod cubemesh;
+mod daycycle;
 mod camera;
 mod chunkmesh;
+mod cli;
+mod controller;
 mod debug;
+mod input;
+mod fluid;
+mod gamepad;
+mod shader;
+mod save;
+mod scene;
+mod settings;
+mod shadow;
+mod sky;
+mod skybox;
+mod spline;
+mod tick;
+mod timeline;
+mod timestep;
+mod block;
+mod lod;
+mod model;
+mod preprocessor;
+mod visibility;
+mod window;
+mod world;
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use clap::Parser;
+
+use controller::CameraController;
 
 extern crate clock_ticks;
 extern crate image;
@@ -12,142 +45,432 @@ use glium::{
         event,
         event::{Event, WindowEvent},
         event_loop::{EventLoop, ControlFlow},  
-        window::WindowBuilder, 
         dpi::PhysicalSize, 
-        ContextBuilder
     }, 
     Surface,
     Display, 
     uniform,
     VertexBuffer, 
     index::PrimitiveType, 
-    Program, 
     IndexBuffer, 
     DrawParameters, 
     draw_parameters
 };
 
-pub const WIDTH: f32 = 1920.0;
-pub const HEIGHT: f32 = 1080.0;
-pub const TITLE: &'static str = "Ferrous OpenGL"; 
-
-fn init() -> (glium::Display, EventLoop<()>) {
-    let events_loop = EventLoop::new();
-    let wb = WindowBuilder::new()
-        .with_inner_size(PhysicalSize::new(WIDTH, HEIGHT))
-        .with_title(TITLE);
-    let cb = ContextBuilder::new().with_depth_buffer(24);
-    let display = Display::new(wb, cb, &events_loop).unwrap();
-    (display, events_loop)
-}
+// default asset directory, overridden by the FERROUS_ASSETS environment variable
+pub const ASSETS: &str = "assets";
+// seconds per game tick, scheduled block updates and random ticks run on ticks
+pub const TICK_LENGTH: f32 = 0.05;
+// seconds per simulation step, the camera and time of day move in these
+pub const STEP_LENGTH: f32 = 1.0 / 60.0;
+// frames longer than this, like after the window was dragged, are cut short
+// rather than caught up on
+pub const MAX_FRAME_TIME: f32 = 0.25;
+// furthest away in blocks a block can be broken or placed
+pub const REACH: f32 = 8.0;
+// the block the Place action puts down
+pub const PLACED_BLOCK: &str = "planks";
+// camera recordings are saved to and played back from here
+pub const RECORDING: &str = "recording.ron";
 
 fn main() {
     // initialize our event_loop, window, OpenGL context, and display
-    let (display, events_loop) = init();
+    let args = cli::Args::parse();
+    let mut settings = or_exit(settings::Settings::load(&args.settings), "settings");
+    args.override_settings(&mut settings);
+    for problem in settings.validate() {
+        eprintln!("{}: {}", args.settings.display(), problem);
+    }
+    println!("Settings Loaded");
+
+    let (display, events_loop) = window::create(&settings.window);
     println!("Window Initialized");
 
     // begin the event loop to keep the window open
-    event_loop(events_loop, display);
+    event_loop(events_loop, display, settings, args);
+}
+
+// hides the cursor and keeps it in the window for mouse look, or frees it,
+// returning whether it's grabbed now
+fn grab_cursor(display: &Display, grab: bool) -> bool {
+    let gl_window = display.gl_window();
+    let window = gl_window.window();
+    // not every platform can grab the cursor, mouse look still works there
+    if let Err(error) = window.set_cursor_grab(grab) {
+        eprintln!("could not {} the cursor: {}", if grab { "grab" } else { "release" }, error);
+    }
+    window.set_cursor_visible(!grab);
+    grab
 }
 
-fn event_loop(event_loop: EventLoop<()>, display: Display) {
-    let mut chunk_mesh = chunkmesh::ChunkMesh::new();
-    chunk_mesh.generate_mesh();
-    // load the cube buffers
+// assets are required to run at all, so report what went wrong and quit
+fn or_exit<T, E: std::fmt::Display>(result: Result<T, E>, what: &str) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("Failed to load {}: {}", what, e);
+        std::process::exit(1);
+    })
+}
+
+fn resize(camera: &mut camera::Camera, size: PhysicalSize<u32>) {
+    // minimised windows are zero sized
+    if size.width > 0 && size.height > 0 {
+        camera.set_aspect_ratio(size.height as f32, size.width as f32);
+    }
+}
+
+fn load_timeline(path: impl AsRef<Path>) -> Result<timeline::Timeline, Box<dyn std::error::Error>> {
+    Ok(timeline::Timeline::from_ron(&std::fs::read_to_string(path)?)?)
+}
+
+// one texture array layer per texture used by the block definitions
+fn load_block_textures(
+    display: &Display,
+    assets: &mut assets::AssetManager,
+    blocks: &block::BlockRegistry,
+) -> Result<glium::texture::SrgbTexture2dArray, Box<dyn std::error::Error>> {
+    let mut images = Vec::new();
+    for name in blocks.texture_names() {
+        let image = assets.load_image(&block::texture_path(name))?;
+        let image_dimensions = image.dimensions();
+        images.push(glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions));
+    }
+    Ok(glium::texture::SrgbTexture2dArray::new(display, images)?)
+}
+
+type ChunkBuffers = HashMap<([i32; 3], usize), (VertexBuffer<cubemesh::PackedVertex>, IndexBuffer<u32>)>;
+
+// (re)creates the buffers for every detail level of a chunk
+fn upload_chunk(display: &Display, chunk_buffers: &mut ChunkBuffers, position: [i32; 3], chunk: &world::Chunk) {
+    for level in 0..lod::LOD_LEVELS {
+        let mesh = chunk.lod_mesh(level);
+        if mesh.indices.is_empty() {
+            chunk_buffers.remove(&(position, level));
+            continue;
+        }
+        let vertices = VertexBuffer::new(display, &mesh.vertices).unwrap();
+        // dynamic so the translucent range can be re-sorted every frame
+        let indices = IndexBuffer::dynamic(display, PrimitiveType::TrianglesList, &mesh.indices).unwrap();
+        chunk_buffers.insert((position, level), (vertices, indices));
+    }
+}
+
+fn event_loop(event_loop: EventLoop<()>, display: Display, settings: settings::Settings, args: cli::Args) {
+    let asset_dir = std::env::var("FERROUS_ASSETS").unwrap_or_else(|_| ASSETS.to_string());
+    let mut assets = assets::AssetManager::new(asset_dir);
+    println!("Loading Assets From {}", assets.root().display());
+
+    let models = or_exit(assets.load_string("models/models.ron"), "block models");
+    let models = or_exit(model::ModelRegistry::from_ron(&models), "block models");
+    println!("Block Models Loaded");
+
+    let blocks = or_exit(block::BlockRegistry::load(&mut assets, models), "block definitions");
+    println!("Block Definitions Loaded");
+
+    // an existing save is loaded, otherwise a new world is made and saved there on quit
+    let mut seed = args.seed.unwrap_or(0);
+    let mut world = match args.save.as_ref().filter(|path| path.exists()) {
+        Some(path) => {
+            let (world, saved_seed) = or_exit(save::load_world(path, &blocks), "saved world");
+            if args.seed.is_some() {
+                eprintln!("ignoring --seed, {} was already generated with seed {}", path.display(), saved_seed);
+            }
+            seed = saved_seed;
+            println!("World Loaded From {}", path.display());
+            world
+        },
+        None => {
+            let world = world::World::generate(seed, &blocks);
+            println!("World Generated");
+            world
+        },
+    };
+
+    // load the chunk buffers
     // TODO: add normals to the cubes and maybe some crude light data
-    let positions = VertexBuffer::new(&display, &chunk_mesh.vertices).unwrap();
-    let indices = IndexBuffer::new(&display, PrimitiveType::TrianglesList, &chunk_mesh.indices).unwrap(); 
-    println!("Cubemesh Initialized");
-
-    use std::io::Cursor;
-    let image = image::load(Cursor::new(&include_bytes!("textures/grass.png")),
-                image::ImageFormat::Png).unwrap().to_rgba8();
-    let image_dimensions = image.dimensions();
-    let image = glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
-    let texture = glium::texture::SrgbTexture2d::new(&display, image).unwrap();
+    let mut chunk_buffers = HashMap::new();
+    for (position, chunk) in world.chunks.iter() {
+        upload_chunk(&display, &mut chunk_buffers, *position, chunk);
+    }
+    println!("Chunkmeshes Initialized");
+
+    let texture = or_exit(load_block_textures(&display, &mut assets, &blocks), "block textures");
     println!("Texture Initialized");
 
-    let program = Program::from_source(
-        &display, 
-        include_str!("shaders/vertex.glsl"), 
-        include_str!("shaders/fragment.glsl"),
-        None)
-        .unwrap();
+    let mut preprocessor = preprocessor::Preprocessor::new(preprocessor::GLSL_VERSION);
+    preprocessor.define_float("AO_STRENGTH", 0.5);
+    preprocessor.define_flag("ENABLE_FOG");
+    preprocessor.define_flag("ENABLE_SHADOWS");
+    preprocessor.define("SHADOW_MAP_SIZE", shadow::SHADOW_MAP_SIZE);
+    // how much light is taken away in shadow
+    preprocessor.define_float("SHADOW_STRENGTH", 0.45);
+    let mut program = or_exit(
+        shader::ShaderProgram::load(&display, &mut assets, &preprocessor, "shaders/vertex.glsl", "shaders/fragment.glsl"),
+        "shaders",
+    );
     println!("Shaders Initialized");
 
-    let mut camera = camera::Camera::new(HEIGHT, WIDTH);
+    let mut sky = or_exit(sky::Sky::new(&display, &mut assets, &preprocessor), "sky");
+    // fog hides the edge of the world, which is this far from the centre
+    let render_distance = (settings.graphics.render_distance as usize * chunkmesh::CHUNK_SIZE) as f32;
+    // a skybox replaces the sky gradient when its images are in the assets
+    let mut skybox = or_exit(skybox::Skybox::load(&display, &mut assets, &preprocessor), "skybox");
+    println!("Sky Initialized");
+
+    let mut shadow_maps = or_exit(shadow::ShadowMaps::new(&display, &mut assets, &preprocessor), "shadow maps");
+    println!("Shadow Maps Initialized");
+
+    let mut scene = or_exit(scene::SceneTarget::new(&display), "scene target");
+    println!("Scene Target Initialized");
+
+    // start in the morning
+    let mut world_time = daycycle::WorldTime::new(0.3);
+
+    let (width, height) = display.get_framebuffer_dimensions();
+    let mut camera = camera::Camera::new(height as f32, width as f32);
+    camera.set_fov(settings.graphics.fov.to_radians());
+    camera.settings = settings.controls.look();
+    camera.set_position(args.position);
+    // SwitchCamera goes through these in order, starting with the first
+    let mut controllers: Vec<Box<dyn CameraController>> = vec![
+        Box::new(controller::FlyController { settings: settings.controls.movement() }),
+        Box::new(controller::WalkController::default()),
+        Box::new(controller::OrbitController::default()),
+        Box::new(controller::SplineController::circle([0.0, 72.0, 0.0], 48.0, 8)),
+    ];
+    let mut active_controller = 0;
+    let mut recorder: Option<timeline::Recorder> = None;
+    // a recording being played back takes over from the active controller,
+    // and how long each frame took is kept for when it finishes
+    let mut playback: Option<(timeline::TimelineController, benchmark::FrameTimes)> = None;
     println!("Camera Initialized");
 
     let mut debug = debug::Debug::new();
 
+    let bindings = or_exit(assets.load_string("bindings.ron"), "key bindings");
+    let mut bindings = or_exit(input::Bindings::from_ron(&bindings), "key bindings");
+    bindings.merge(&settings.controls.bindings);
+    let mut input = input::Input::new(bindings);
+    let mut gamepads = gamepad::Gamepads::new();
+    let placed_block = or_exit(blocks.id(PLACED_BLOCK).ok_or("no such block"), PLACED_BLOCK);
+    println!("Input Initialized");
+
     let mut previous_clock = clock_ticks::precise_time_ns();
+    let mut steps = timestep::FixedTimestep::new(STEP_LENGTH, MAX_FRAME_TIME);
+    let mut ticks = timestep::FixedTimestep::new(TICK_LENGTH, MAX_FRAME_TIME);
    
-    display.gl_window().window().set_cursor_grab(true).unwrap();
-    display.gl_window().window().set_cursor_visible(false);
+    // a benchmark plays its timeline from the first frame and quits at the end
+    if let Some(path) = &args.benchmark {
+        let timeline = or_exit(load_timeline(path), "benchmark timeline");
+        let mut player = timeline::TimelineController::new(timeline);
+        let context = controller::ControllerContext { input: &input, world: &world, blocks: &blocks };
+        player.activate(&mut camera, &context);
+        playback = Some((player, benchmark::FrameTimes::new()));
+        println!("Benchmarking {}", path.display());
+    }
 
-    event_loop.run(move |ev, _, control_flow| {
+    let mut cursor_grabbed = grab_cursor(&display, args.benchmark.is_none());
 
-        let now = clock_ticks::precise_time_ns();
-        let dt = now - previous_clock;
-
-        let next_frame_time = std::time::Instant::now() +
-            std::time::Duration::from_nanos(16_666_667);
-        *control_flow = ControlFlow::WaitUntil(next_frame_time);
-        
-        // update camera position
-        camera.update((dt as f32) / 1000000000f32);
+    event_loop.run(move |ev, _, control_flow| {
 
         match ev {
-            // TODO: eventually move to its own "input.rs" file
             Event::DeviceEvent { event, .. } => {
-                match event {
-                    //event::DeviceEvent::Key(KeyboardInput { virtual_keycode, .. }) => {
-                    //    match virtual_keycode.unwrap() {
-                    //        VirtualKeyCode::Escape => {
-                    //            *control_flow = ControlFlow::Exit;
-                    //            return;
-                    //        },
-                    //        _ => {},
-                    //    }
-                    //},
-                    event => {
-                        camera.parse_input(&event);
-                        debug.parse_input(&event, control_flow);
-                    }
-                }
+                input.parse_device_event(&event);
+                return;
             },
             // close the window if asked to
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => {
-                    *control_flow = ControlFlow::Exit;
-                    return;
-                },
-                _ => {},
+            Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                *control_flow = ControlFlow::Exit;
+                return;
+            },
+            // the frame is drawn at whatever size the window is, the camera
+            // only needs to keep the picture from stretching. the scene
+            // target catches up with the window when it's next drawn and
+            // the shadow maps have their own size
+            Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                resize(&mut camera, size);
+                return;
+            },
+            // moving to a monitor with a different scale factor resizes the
+            // window to the size suggested for it
+            Event::WindowEvent { event: WindowEvent::ScaleFactorChanged { new_inner_size, .. }, .. } => {
+                resize(&mut camera, *new_inner_size);
+                return;
+            },
+            // let go of the mouse and any held keys when switching to another window
+            Event::WindowEvent { event: WindowEvent::Focused(false), .. } => {
+                cursor_grabbed = grab_cursor(&display, false);
+                input.release_all();
+                return;
+            },
+            Event::WindowEvent { event, .. } => {
+                input.parse_window_event(&event);
+                return;
             },
+            Event::LoopDestroyed => {
+                if let Some(path) = &args.save {
+                    match save::save_world(path, &world, seed, &blocks) {
+                        Ok(()) => println!("World Saved To {}", path.display()),
+                        Err(error) => eprintln!("could not save the world to {}: {}", path.display(), error),
+                    }
+                }
+                return;
+            },
+            // only update and draw when the frame timer fires, not on every input event
             Event::NewEvents(cause) => match cause {
                 event::StartCause::ResumeTimeReached { .. } => (),
+                event::StartCause::Poll => (),
                 event::StartCause::Init => println!("Loop Initialized"),
-                _ => {},
+                _ => return,
+            },
+            _ => return,
+        }
+
+        // replays and benchmarks draw frames as fast as they can, so the
+        // frame times measure the renderer rather than the frame timer
+        if playback.is_some() {
+            *control_flow = ControlFlow::Poll;
+        } else {
+            let next_frame_time = std::time::Instant::now() +
+                std::time::Duration::from_nanos(16_666_667);
+            *control_flow = ControlFlow::WaitUntil(next_frame_time);
+        }
+
+        let now = clock_ticks::precise_time_ns();
+        let dt = (now - previous_clock) as f32 / 1000000000f32;
+        previous_clock = now;
+
+        gamepads.poll(&mut input);
+        debug.update(&input, control_flow);
+        world_time.update(&input);
+
+        // breaking and placing go before grabbing, so the click that grabs
+        // the cursor doesn't also break a block
+        if cursor_grabbed {
+            let target = world.raycast(&blocks, camera.position(), camera.forward, REACH);
+            if let Some((hit, before)) = target {
+                if input.is_pressed(input::Action::Break) {
+                    world.set_block(hit, block::AIR, &blocks);
+                } else if input.is_pressed(input::Action::Place) {
+                    world.set_block(before, placed_block, &blocks);
+                }
+            }
+        }
+        if input.is_pressed(input::Action::ToggleFullscreen) {
+            let fullscreen = !window::is_fullscreen(&display);
+            window::set_fullscreen(&display, fullscreen.then_some(settings.window.fullscreen_mode));
+        }
+        if input.is_pressed(input::Action::ReleaseCursor) && cursor_grabbed {
+            cursor_grabbed = grab_cursor(&display, false);
+        } else if input.is_pressed(input::Action::GrabCursor) && !cursor_grabbed {
+            cursor_grabbed = grab_cursor(&display, true);
+        }
+
+        // the mouse only turns the camera while it's grabbed, sticks always do
+        let mouse = if cursor_grabbed { input.mouse_delta() } else { (0.0, 0.0) };
+        let stick = [
+            input.value(input::Action::LookRight) - input.value(input::Action::LookLeft),
+            input.value(input::Action::LookUp) - input.value(input::Action::LookDown),
+        ];
+        let turn = camera.look_angles(mouse, stick, dt);
+
+        let context = controller::ControllerContext { input: &input, world: &world, blocks: &blocks };
+        if input.is_pressed(input::Action::SwitchCamera) {
+            active_controller = (active_controller + 1) % controllers.len();
+            controllers[active_controller].activate(&mut camera, &context);
+            println!("Camera Controller: {}", controllers[active_controller].name());
+        }
+        if input.is_pressed(input::Action::SwitchProjection) {
+            camera.projection = camera.projection.next();
+            println!("Camera Projection: {:?}", camera.projection);
+        }
+        // field of view in 5 degree steps, and the near and far planes halved or doubled
+        let fov_step = 5f32.to_radians();
+        if input.is_pressed(input::Action::WidenView) || input.is_pressed(input::Action::NarrowView) {
+            let step = if input.is_pressed(input::Action::WidenView) { fov_step } else { -fov_step };
+            camera.set_fov(camera.fov() + step);
+            println!("Field Of View: {:.0}", camera.fov().to_degrees());
+        }
+        let (near, far) = (camera.near(), camera.far());
+        let clip_planes = [
+            (input::Action::NearPlaneCloser, near / 2.0, far),
+            (input::Action::NearPlaneFurther, near * 2.0, far),
+            (input::Action::FarPlaneCloser, near, far / 2.0),
+            (input::Action::FarPlaneFurther, near, far * 2.0),
+        ];
+        for (action, near, far) in clip_planes {
+            if input.is_pressed(action) {
+                camera.set_clip_planes(near, far);
+                println!("Clip Planes: {} to {}", camera.near(), camera.far());
+            }
+        }
+        if input.is_pressed(input::Action::Record) {
+            match recorder.take() {
+                Some(recording) => match std::fs::write(RECORDING, recording.finish().to_ron()) {
+                    Ok(()) => println!("Recording Saved To {}", RECORDING),
+                    Err(error) => eprintln!("could not save {}: {}", RECORDING, error),
+                },
+                None => {
+                    recorder = Some(timeline::Recorder::new());
+                    println!("Recording Started");
+                },
+            }
+        }
+        if input.is_pressed(input::Action::PlayRecording) && playback.is_none() {
+            match load_timeline(RECORDING) {
+                Ok(timeline) => {
+                    let mut player = timeline::TimelineController::new(timeline);
+                    player.activate(&mut camera, &context);
+                    playback = Some((player, benchmark::FrameTimes::new()));
+                    println!("Playing {}", RECORDING);
+                },
+                Err(error) => eprintln!("could not play {}: {}", RECORDING, error),
+            }
+        }
+
+        let active: &mut dyn CameraController = match playback.as_mut() {
+            Some((player, frame_times)) => {
+                frame_times.push(dt);
+                player
             },
-            _ => {},
+            None => controllers[active_controller].as_mut(),
+        };
+        active.look(&mut camera, turn);
+
+        // the camera and time of day move in fixed steps whatever the frame
+        // rate is, and the camera is drawn between its last two steps
+        for _ in 0..steps.advance(dt) {
+            camera.begin_step();
+            active.update(&mut camera, &context, steps.step());
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.record(&camera, steps.step());
+            }
+            world_time.advance(steps.step());
+        }
+        if let Some((_, frame_times)) = playback.take_if(|(player, _)| player.finished()) {
+            println!("Playback Finished: {}", frame_times.summary());
+            controllers[active_controller].activate(&mut camera, &context);
+            if args.benchmark.is_some() {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+        camera.set_interpolation(steps.alpha());
+        input.end_frame();
+
+        // game ticks run at their own slower rate
+        for _ in 0..ticks.advance(dt) {
+            for position in world.tick(&blocks) {
+                upload_chunk(&display, &mut chunk_buffers, position, &world.chunks[&position]);
+            }
         }
 
-        let _ = display.gl_window().window().set_cursor_position(glium::glutin::dpi::LogicalPosition::new(WIDTH/2f32, HEIGHT/2f32));
-        
         let perspective = camera.perspective_matrix();
         let view = camera.view_matrix();
-        let model = [
-            [ 1.0, 0.0, 0.0, 0.0 ],
-            [ 0.0, 1.0, 0.0, 0.0 ],
-            [ 0.0, 0.0, 1.0, 0.0 ],
-            [ -16.0, -32.0, -16.0, 1.0f32 ]
-        ];
 
         let draw_mode = debug.get_draw_state();
 
         let params = DrawParameters {
             depth: glium::Depth { 
-                test: draw_parameters::DepthTest::IfLess, 
+                test: camera.depth_test(), 
                 write: true,
                 .. Default::default() 
             },
@@ -160,15 +483,164 @@ fn event_loop(event_loop: EventLoop<()>, display: Display) {
             .. Default::default()
         };
 
-        previous_clock = now;
+        // translucent faces are blended over the opaque world without writing depth,
+        // so they don't hide each other
+        let translucent_params = DrawParameters {
+            depth: glium::Depth { 
+                test: camera.depth_test(), 
+                write: false,
+                .. Default::default() 
+            },
+            blend: glium::Blend::alpha_blending(),
+            polygon_mode: draw_mode,
+            .. Default::default()
+        };
 
         // create the target and clear the color and depth buffers
         // draw the frame
-        let mut target = display.draw();
-        target.clear_color_and_depth((0.0, 1.0, 1.0, 1.0), 1.0);
-        target.draw(&positions, &indices, &program, 
-            &uniform! { model: model, view: view, perspective: perspective, tex: &texture }, &params).unwrap();
-        target.finish().unwrap();
+        program.reload_if_changed(&display, &mut assets);
+        sky.reload_if_changed(&display, &mut assets);
+        if let Some(skybox) = skybox.as_mut() {
+            skybox.reload_if_changed(&display, &mut assets);
+        }
+        shadow_maps.reload_if_changed(&display, &mut assets);
+
+        let time = world_time.time();
+        let size = chunkmesh::CHUNK_SIZE as f32;
+        let eye = camera.position();
+        let camera_chunk = world::World::chunk_at(eye);
+
+        // shadow pass, every chunk is drawn into each cascade since blocks
+        // the camera can't see can still cast shadows it can
+        let sun_direction = daycycle::sun_direction(time);
+        let light_direction = if sun_direction[1] >= 0.0 {
+            sun_direction
+        } else {
+            [-sun_direction[0], -sun_direction[1], -sun_direction[2]]
+        };
+        let frustum = shadow::Frustum {
+            position: eye,
+            forward: camera.forward,
+            fov: camera.fov(),
+            aspect: 1.0 / camera.aspect_ratio(),
+        };
+        let cascades = shadow::fit_cascades(&frustum, camera.near(), light_direction);
+        // LOD faces stretch their uvs over several blocks, so the texture has to tile
+        let block_texture = texture.sampled().wrap_function(glium::uniforms::SamplerWrapFunction::Repeat);
+        let shadow_params = DrawParameters {
+            depth: glium::Depth {
+                test: draw_parameters::DepthTest::IfLess,
+                write: true,
+                .. Default::default()
+            },
+            .. Default::default()
+        };
+        for (i, cascade) in cascades.iter().enumerate() {
+            let mut framebuffer = shadow_maps.framebuffer(&display, i);
+            framebuffer.clear_depth(1.0);
+            for (chunk, data) in world.chunks.iter() {
+                let level = lod::select_level(camera_chunk, *chunk);
+                let (vertices, indices) = match chunk_buffers.get(&(*chunk, level)) {
+                    Some(buffers) => buffers,
+                    None => continue,
+                };
+                let mesh = data.lod_mesh(level);
+                let opaque = match indices.slice(mesh.opaque_range()) {
+                    Some(slice) if !mesh.opaque_range().is_empty() => slice,
+                    _ => continue,
+                };
+                let chunk_offset = [chunk[0] as f32 * size, chunk[1] as f32 * size, chunk[2] as f32 * size];
+                framebuffer.draw(vertices, opaque, shadow_maps.program(),
+                    &uniform! { chunk_offset: chunk_offset, light_matrix: cascade.matrix, tex: block_texture,
+                        alpha_cutoff: 0.5f32 }, &shadow_params).unwrap();
+            }
+        }
+
+        // the world is drawn into the scene target for its float depth buffer
+        scene.resize(&display);
+        let mut target = scene.framebuffer(&display);
+        target.clear_depth(camera.clear_depth());
+        scene.set_zero_to_one_depth(camera.is_reverse_z());
+        let sky_colors = daycycle::sky_colors(time);
+        let sun_color = daycycle::sun_color(time);
+        let sky_light = daycycle::sky_light(time);
+        if skybox.is_none() {
+            sky.draw(&mut target, view, camera.sky_perspective_matrix(), &sky_colors, sun_direction, sun_color);
+        }
+
+        let fog = sky::Fog::new(debug.get_fog_mode(), render_distance);
+        let fog_mode = match fog.mode {
+            sky::FogMode::Linear => 0,
+            sky::FogMode::Exponential => 1,
+        };
+        // uniforms shared by both world passes
+        let world_uniforms = |chunk_offset: [f32; 3], alpha_cutoff: f32, opacity: f32| uniform! {
+            chunk_offset: chunk_offset, view: view, perspective: perspective, tex: block_texture,
+            alpha_cutoff: alpha_cutoff, opacity: opacity,
+            eye: eye, fog_mode: fog_mode, fog_start: fog.start, fog_end: fog.end, fog_density: fog.density,
+            sky_horizon: sky_colors.horizon, sky_zenith: sky_colors.zenith,
+            sun_color: sun_color, sky_light: sky_light,
+            shadow_maps: shadow_maps.sampled(), light_direction: light_direction,
+            light_matrix0: cascades[0].matrix, light_matrix1: cascades[1].matrix, light_matrix2: cascades[2].matrix,
+            cascade_far: [cascades[0].far, cascades[1].far, cascades[2].far],
+            cascade_texel: [cascades[0].texel, cascades[1].texel, cascades[2].texel],
+        };
+        let mut visible = world.visible_chunks(eye);
+        // chunks past the render distance are hidden in the fog anyway
+        let distance = settings.graphics.render_distance as i32;
+        visible.retain(|chunk| {
+            (chunk[0] - camera_chunk[0]).abs() <= distance && (chunk[2] - camera_chunk[2]).abs() <= distance
+        });
+
+        // opaque and cutout pass
+        for chunk in visible.iter() {
+            let level = lod::select_level(camera_chunk, *chunk);
+            let (vertices, indices) = match chunk_buffers.get(&(*chunk, level)) {
+                Some(buffers) => buffers,
+                None => continue,
+            };
+            let mesh = world.chunks[chunk].lod_mesh(level);
+            let opaque = match indices.slice(mesh.opaque_range()) {
+                Some(slice) if !mesh.opaque_range().is_empty() => slice,
+                _ => continue,
+            };
+            let chunk_offset = [chunk[0] as f32 * size, chunk[1] as f32 * size, chunk[2] as f32 * size];
+            target.draw(vertices, opaque, program.program(), &world_uniforms(chunk_offset, 0.5, 1.0), &params).unwrap();
+        }
+
+        // the skybox is drawn after the opaque world so it only covers pixels
+        // nothing was drawn to, translucent faces are blended over it afterwards
+        if let Some(skybox) = &skybox {
+            let tint = [sun_color[0] * sky_light, sun_color[1] * sky_light, sun_color[2] * sky_light];
+            skybox.draw(&mut target, camera.rotation_matrix(), camera.sky_perspective_matrix(), tint, camera.is_reverse_z());
+        }
+
+        // translucent pass, furthest chunks first
+        let chunk_distance = |chunk: &[i32; 3]| {
+            (0..3).map(|i| ((chunk[i] as f32 + 0.5) * size - eye[i]).powi(2)).sum::<f32>()
+        };
+        visible.sort_by(|a, b| chunk_distance(b).partial_cmp(&chunk_distance(a)).unwrap_or(std::cmp::Ordering::Equal));
+        for chunk in visible.iter() {
+            let level = lod::select_level(camera_chunk, *chunk);
+            let (vertices, indices) = match chunk_buffers.get(&(*chunk, level)) {
+                Some(buffers) => buffers,
+                None => continue,
+            };
+            let mesh = world.chunks[chunk].lod_mesh(level);
+            let translucent = match indices.slice(mesh.translucent_range()) {
+                Some(slice) if !mesh.translucent_range().is_empty() => slice,
+                _ => continue,
+            };
+            let chunk_offset = [chunk[0] as f32 * size, chunk[1] as f32 * size, chunk[2] as f32 * size];
+            let local_eye = [eye[0] - chunk_offset[0], eye[1] - chunk_offset[1], eye[2] - chunk_offset[2]];
+            translucent.write(&mesh.sorted_translucent_indices(local_eye));
+            target.draw(vertices, translucent, program.program(), &world_uniforms(chunk_offset, 0.0, 0.6), &translucent_params).unwrap();
+        }
+        scene.set_zero_to_one_depth(false);
+
+        let frame = display.draw();
+        scene.present(&display, &frame);
+        frame.finish().unwrap();
     });
 }
 
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::cubemesh::face_vertices;
+use crate::visibility::Face;
+
+// models are authored in sixteenths of a voxel, like minecraft's block models
+pub const MODEL_UNITS: f32 = 16.0;
+
+/// A block model as written in a model file, made of boxes.
+#[derive(Deserialize, Debug)]
+pub struct BlockModel {
+    pub elements: Vec<ModelElement>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ModelElement {
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+    // degrees around the y axis through the centre of the element
+    #[serde(default)]
+    pub rotation: f32,
+    // stretch a rotated element so it still spans the whole block, used for plants
+    #[serde(default)]
+    pub rescale: bool,
+    // faces to draw, every face with uvs taken from `from` and `to` when empty
+    #[serde(default)]
+    pub faces: HashMap<Face, ModelFace>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct ModelFace {
+    // u1, v1, u2, v2 in model units
+    pub uv: Option<[f32; 4]>,
+}
+
+/// A model face ready to be copied into a chunk mesh.
+#[derive(Clone, Copy, Debug)]
+pub struct BakedQuad {
+    pub face: Face,
+    // block local positions and uvs in model units
+    pub positions: [[f32; 3]; 4],
+    pub uvs: [[f32; 2]; 4],
+    // face of the block this quad lies flat against, it is hidden when the
+    // neighbour on that side has a full face there
+    pub cullface: Option<Face>,
+}
+
+pub struct BakedModel {
+    pub quads: Vec<BakedQuad>,
+    // bitmask of the block faces that are completely covered by the model
+    full_faces: u8,
+}
+
+impl BakedModel {
+    pub fn is_full(&self, face: Face) -> bool {
+        self.full_faces & (1 << face as u8) != 0
+    }
+
+    fn bake(model: &BlockModel) -> Self {
+        let mut quads = Vec::new();
+        // 16x16 coverage grid for each of the six block faces
+        let mut coverage = [[false; 256]; 6];
+
+        for element in model.elements.iter() {
+            let faces: Vec<(Face, Option<[f32; 4]>)> = if element.faces.is_empty() {
+                Face::ALL.iter().map(|&f| (f, None)).collect()
+            } else {
+                element.faces.iter().map(|(&f, m)| (f, m.uv)).collect()
+            };
+
+            for (face, uv) in faces {
+                let quad = bake_quad(element, face, uv);
+                if let Some(cullface) = quad.cullface {
+                    mark_coverage(&mut coverage[cullface as usize], element, face);
+                }
+                quads.push(quad);
+            }
+        }
+
+        let mut full_faces = 0;
+        for face in Face::ALL {
+            if coverage[face as usize].iter().all(|&c| c) {
+                full_faces |= 1 << face as u8;
+            }
+        }
+        Self { quads, full_faces }
+    }
+}
+
+// the two axes a face of the given direction spans
+fn tangent_axes(face: Face) -> (usize, usize) {
+    match face {
+        Face::East | Face::West => (1, 2),
+        Face::Top | Face::Bottom => (0, 2),
+        Face::North | Face::South => (0, 1),
+    }
+}
+
+fn normal_axis(face: Face) -> usize {
+    face.offset().iter().position(|&n| n != 0).unwrap()
+}
+
+fn bake_quad(element: &ModelElement, face: Face, uv: Option<[f32; 4]>) -> BakedQuad {
+    let (t1, t2) = tangent_axes(face);
+    let uv = uv.unwrap_or([element.from[t1], element.from[t2], element.to[t1], element.to[t2]]);
+    let center = [
+        (element.from[0] + element.to[0]) / 2.0,
+        (element.from[1] + element.to[1]) / 2.0,
+        (element.from[2] + element.to[2]) / 2.0,
+    ];
+    let (sin, cos) = element.rotation.to_radians().sin_cos();
+    let stretch = if element.rescale && cos.abs() > f32::EPSILON { 1.0 / cos.abs() } else { 1.0 };
+
+    let mut positions = [[0.0; 3]; 4];
+    let mut uvs = [[0.0; 2]; 4];
+    // reuse the corner layout and winding of the unit cube faces
+    for (i, vertex) in face_vertices(face).iter().enumerate() {
+        let corner = vertex.corner();
+        let mut p = [0.0; 3];
+        for axis in 0..3 {
+            p[axis] = if corner[axis] == 0 { element.from[axis] } else { element.to[axis] };
+        }
+        if element.rotation != 0.0 {
+            let (x, z) = ((p[0] - center[0]) * stretch, (p[2] - center[2]) * stretch);
+            p[0] = center[0] + x * cos - z * sin;
+            p[2] = center[2] + x * sin + z * cos;
+        }
+        positions[i] = p;
+
+        let uv_corner = vertex.uv_corner();
+        uvs[i] = [
+            if uv_corner[0] == 0 { uv[0] } else { uv[2] },
+            if uv_corner[1] == 0 { uv[1] } else { uv[3] },
+        ];
+    }
+
+    // a face is only on the block boundary if it wasn't rotated off of it
+    let axis = normal_axis(face);
+    let boundary = if face.offset()[axis] > 0 { MODEL_UNITS } else { 0.0 };
+    let plane = if face.offset()[axis] > 0 { element.to[axis] } else { element.from[axis] };
+    let cullface = if element.rotation == 0.0 && plane == boundary { Some(face) } else { None };
+
+    BakedQuad { face, positions, uvs, cullface }
+}
+
+fn mark_coverage(coverage: &mut [bool; 256], element: &ModelElement, face: Face) {
+    let (t1, t2) = tangent_axes(face);
+    let range = |axis: usize| {
+        let from = element.from[axis].max(0.0).floor() as usize;
+        let to = element.to[axis].min(MODEL_UNITS).ceil() as usize;
+        from..to
+    };
+    for a in range(t1) {
+        for b in range(t2) {
+            coverage[a * 16 + b] = true;
+        }
+    }
+}
+
+/// Every block model, baked and looked up by name.
+pub struct ModelRegistry {
+    models: HashMap<String, BakedModel>,
+}
+
+impl ModelRegistry {
+    // parses a RON map of model names to models
+    pub fn from_ron(source: &str) -> Result<Self, ron::error::SpannedError> {
+        let models: HashMap<String, BlockModel> = ron::from_str(source)?;
+        let models = models
+            .iter()
+            .map(|(name, model)| (name.clone(), BakedModel::bake(model)))
+            .collect();
+        Ok(Self { models })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BakedModel> {
+        self.models.get(name)
+    }
+}
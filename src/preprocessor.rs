@@ -0,0 +1,274 @@
+use std::fmt;
+
+use crate::assets::AssetError;
+
+// every shader is compiled as this version, whatever its own #version line says
+pub const GLSL_VERSION: u32 = 150;
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    Asset(AssetError),
+    MalformedInclude { path: String, line: usize },
+    IncludeCycle { path: String },
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PreprocessError::Asset(error) => error.fmt(f),
+            PreprocessError::MalformedInclude { path, line } => {
+                write!(f, "{}:{}: expected #include \"file\"", path, line)
+            },
+            PreprocessError::IncludeCycle { path } => write!(f, "{} includes itself", path),
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+impl From<AssetError> for PreprocessError {
+    fn from(error: AssetError) -> Self {
+        PreprocessError::Asset(error)
+    }
+}
+
+/// A shader after preprocessing, along with every file it was built from.
+pub struct Preprocessed {
+    pub source: String,
+    pub files: Vec<String>,
+}
+
+/// A small GLSL preprocessor. It replaces `#version` lines with a single
+/// normalised one, injects `#define`s set from Rust and pastes in
+/// `#include "file"`s, resolved relative to the including file. Each file is
+/// only included once. `#line` directives around each include keep compile
+/// errors pointing at the right line, with the source string number being the
+/// file's index in `Preprocessed::files`. Loading is left to the caller so
+/// this stays plain string processing.
+#[derive(Clone)]
+pub struct Preprocessor {
+    version: u32,
+    defines: Vec<(String, String)>,
+}
+
+impl Preprocessor {
+    pub fn new(version: u32) -> Self {
+        Self {
+            version,
+            defines: Vec::new(),
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: impl fmt::Display) -> &mut Self {
+        self.defines.retain(|(n, _)| n != name);
+        self.defines.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // floats always get a decimal point so GLSL doesn't read them as ints
+    pub fn define_float(&mut self, name: &str, value: f32) -> &mut Self {
+        self.define(name, format!("{:?}", value))
+    }
+
+    // a define with no value, for #ifdef
+    pub fn define_flag(&mut self, name: &str) -> &mut Self {
+        self.define(name, "")
+    }
+
+    pub fn process(
+        &self,
+        path: &str,
+        load: &mut dyn FnMut(&str) -> Result<String, AssetError>,
+    ) -> Result<Preprocessed, PreprocessError> {
+        let mut output = Preprocessed {
+            source: format!("#version {}\n", self.version),
+            files: Vec::new(),
+        };
+        for (name, value) in self.defines.iter() {
+            if value.is_empty() {
+                output.source.push_str(&format!("#define {}\n", name));
+            } else {
+                output.source.push_str(&format!("#define {} {}\n", name, value));
+            }
+        }
+        self.expand(path, load, &mut output, &mut Vec::new())?;
+        Ok(output)
+    }
+
+    fn expand(
+        &self,
+        path: &str,
+        load: &mut dyn FnMut(&str) -> Result<String, AssetError>,
+        output: &mut Preprocessed,
+        stack: &mut Vec<String>,
+    ) -> Result<(), PreprocessError> {
+        if stack.iter().any(|p| p == path) {
+            return Err(PreprocessError::IncludeCycle { path: path.to_string() });
+        }
+        if output.files.iter().any(|p| p == path) {
+            return Ok(());
+        }
+        let index = output.files.len();
+        output.files.push(path.to_string());
+        stack.push(path.to_string());
+
+        let source = load(path)?;
+        // GLSL 150 numbers the line after a #line directive one higher than it says
+        output.source.push_str(&format!("#line 0 {}\n", index));
+        for (number, line) in source.lines().enumerate() {
+            let directive = line.trim_start();
+            if directive.starts_with("#version") {
+                // keep the line so the numbering still matches the file
+                output.source.push('\n');
+                continue;
+            }
+            if let Some(rest) = directive.strip_prefix("#include") {
+                let name = rest
+                    .trim()
+                    .strip_prefix('"')
+                    .and_then(|r| r.strip_suffix('"'))
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| PreprocessError::MalformedInclude { path: path.to_string(), line: number + 1 })?;
+                self.expand(&resolve(path, name), load, output, stack)?;
+                output.source.push_str(&format!("#line {} {}\n", number + 1, index));
+                continue;
+            }
+            output.source.push_str(line);
+            output.source.push('\n');
+        }
+
+        stack.pop();
+        Ok(())
+    }
+}
+
+// path of an include relative to the directory of the file including it
+pub fn resolve(from: &str, include: &str) -> String {
+    let mut parts: Vec<&str> = from.split('/').collect();
+    parts.pop();
+    for part in include.split('/') {
+        match part {
+            "" | "." => {},
+            ".." => {
+                parts.pop();
+            },
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn process(preprocessor: &Preprocessor, files: &[(&str, &str)], path: &str) -> Result<Preprocessed, PreprocessError> {
+        let files: HashMap<&str, &str> = files.iter().cloned().collect();
+        let mut load = |path: &str| {
+            files.get(path)
+                .map(|source| source.to_string())
+                .ok_or_else(|| AssetError::NotFound { path: path.to_string() })
+        };
+        preprocessor.process(path, &mut load)
+    }
+
+    // the source without #version, #define and #line lines
+    fn code(source: &str) -> Vec<&str> {
+        source.lines()
+            .filter(|line| !line.starts_with('#') && !line.is_empty())
+            .collect()
+    }
+
+    #[test]
+    fn nested_includes() {
+        let files = [
+            ("shaders/main.glsl", "#include \"a.glsl\"\nmain"),
+            ("shaders/a.glsl", "#include \"b.glsl\"\na"),
+            ("shaders/b.glsl", "b"),
+        ];
+        let output = process(&Preprocessor::new(150), &files, "shaders/main.glsl").unwrap();
+        assert_eq!(code(&output.source), ["b", "a", "main"]);
+        assert_eq!(output.files, ["shaders/main.glsl", "shaders/a.glsl", "shaders/b.glsl"]);
+    }
+
+    #[test]
+    fn include_cycle() {
+        let files = [
+            ("main.glsl", "#include \"a.glsl\""),
+            ("a.glsl", "#include \"b.glsl\""),
+            ("b.glsl", "#include \"a.glsl\""),
+        ];
+        match process(&Preprocessor::new(150), &files, "main.glsl") {
+            Err(PreprocessError::IncludeCycle { path }) => assert_eq!(path, "a.glsl"),
+            _ => panic!("expected an include cycle"),
+        }
+    }
+
+    #[test]
+    fn included_once() {
+        let files = [
+            ("main.glsl", "#include \"a.glsl\"\n#include \"b.glsl\"\n#include \"a.glsl\""),
+            ("a.glsl", "a"),
+            ("b.glsl", "#include \"a.glsl\"\nb"),
+        ];
+        let output = process(&Preprocessor::new(150), &files, "main.glsl").unwrap();
+        assert_eq!(code(&output.source), ["a", "b"]);
+    }
+
+    #[test]
+    fn malformed_include() {
+        for include in ["#include a.glsl", "#include \"a.glsl", "#include \"\"", "#include"] {
+            let files = [("main.glsl", format!("void main() {{}}\n{}", include))];
+            let files: Vec<(&str, &str)> = files.iter().map(|(p, s)| (*p, s.as_str())).collect();
+            match process(&Preprocessor::new(150), &files, "main.glsl") {
+                Err(PreprocessError::MalformedInclude { path, line }) => {
+                    assert_eq!(path, "main.glsl");
+                    assert_eq!(line, 2);
+                },
+                _ => panic!("expected {} to be malformed", include),
+            }
+        }
+    }
+
+    #[test]
+    fn defines_after_version() {
+        let mut preprocessor = Preprocessor::new(150);
+        preprocessor.define("SIZE", 4).define_float("SCALE", 2.0).define_flag("FOG");
+        let files = [("main.glsl", "#version 330 core\nmain")];
+        let output = process(&preprocessor, &files, "main.glsl").unwrap();
+        let lines: Vec<&str> = output.source.lines().collect();
+        assert_eq!(lines[..4], ["#version 150", "#define SIZE 4", "#define SCALE 2.0", "#define FOG"]);
+        assert_eq!(output.source.matches("#version").count(), 1);
+    }
+
+    #[test]
+    fn line_directives() {
+        let files = [
+            ("main.glsl", "#version 330\n#include \"a.glsl\"\nmain"),
+            ("a.glsl", "a"),
+        ];
+        let output = process(&Preprocessor::new(150), &files, "main.glsl").unwrap();
+        assert_eq!(output.source, "#version 150\n#line 0 0\n\n#line 0 1\na\n#line 2 0\nmain\n");
+    }
+
+    #[test]
+    fn resolve_paths() {
+        assert_eq!(resolve("shaders/world.glsl", "common/fog.glsl"), "shaders/common/fog.glsl");
+        assert_eq!(resolve("shaders/world/chunk.glsl", "../fog.glsl"), "shaders/fog.glsl");
+        assert_eq!(resolve("shaders/world.glsl", "./fog.glsl"), "shaders/fog.glsl");
+        assert_eq!(resolve("world.glsl", "fog.glsl"), "fog.glsl");
+    }
+
+    #[test]
+    fn include_through_parent() {
+        let files = [
+            ("shaders/world/chunk.glsl", "#include \"../common/fog.glsl\""),
+            ("shaders/common/fog.glsl", "fog"),
+        ];
+        let output = process(&Preprocessor::new(150), &files, "shaders/world/chunk.glsl").unwrap();
+        assert_eq!(output.files, ["shaders/world/chunk.glsl", "shaders/common/fog.glsl"]);
+        assert_eq!(code(&output.source), ["fog"]);
+    }
+}
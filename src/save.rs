@@ -0,0 +1,210 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::block::BlockRegistry;
+use crate::chunkmesh::{VoxelMap, CHUNK_SIZE};
+use crate::world::World;
+
+// saves start with these, then the version they were written with
+const MAGIC: &[u8; 4] = b"FRWD";
+const SAVE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    NotASave,
+    Version { found: u32 },
+    // the save has a block the block definitions don't
+    UnknownBlock { name: String },
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "{}", e),
+            SaveError::NotASave => write!(f, "not a saved world"),
+            SaveError::Version { found } => {
+                write!(f, "save version {} is not supported, only version {} is", found, SAVE_VERSION)
+            },
+            SaveError::UnknownBlock { name } => write!(f, "the world has a block \"{}\" that is not defined", name),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+/// Writes every chunk's blocks to `path`, along with the name of each block
+/// id so the save can be read back if the ids change. Scheduled block
+/// updates aren't saved, fluids start flowing again when it's loaded.
+///
+/// Everything is little endian: the magic and version, the seed, the block
+/// names as a count then each name's u32 byte length and bytes, then the
+/// chunk count and each chunk's position followed by its blocks one byte each.
+pub fn save_world(path: impl AsRef<Path>, world: &World, seed: u32, blocks: &BlockRegistry) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&SAVE_VERSION.to_le_bytes())?;
+    file.write_all(&seed.to_le_bytes())?;
+
+    let names = blocks.names();
+    file.write_all(&(names.len() as u32).to_le_bytes())?;
+    for name in names {
+        file.write_all(&(name.len() as u32).to_le_bytes())?;
+        file.write_all(name.as_bytes())?;
+    }
+
+    // sorted so the same world always saves the same
+    let mut positions: Vec<_> = world.chunks.keys().copied().collect();
+    positions.sort();
+    file.write_all(&(positions.len() as u32).to_le_bytes())?;
+    for position in positions {
+        for coordinate in position {
+            file.write_all(&coordinate.to_le_bytes())?;
+        }
+        let voxels = world.chunks[&position].mesh.voxels();
+        let bytes: Vec<u8> = voxels.iter().flatten().flatten().map(|&b| b as u8).collect();
+        file.write_all(&bytes)?;
+    }
+    file.flush()
+}
+
+fn read_u32(file: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    file.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Reads a world written by `save_world`, returning it with its seed.
+pub fn load_world(path: impl AsRef<Path>, blocks: &BlockRegistry) -> Result<(World, u32), SaveError> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(SaveError::NotASave);
+    }
+    let version = read_u32(&mut file)?;
+    if version != SAVE_VERSION {
+        return Err(SaveError::Version { found: version });
+    }
+    let seed = read_u32(&mut file)?;
+
+    // block ids as they were saved to ids as they are now
+    let mut ids = Vec::new();
+    for _ in 0..read_u32(&mut file)? {
+        let mut name = vec![0; read_u32(&mut file)? as usize];
+        file.read_exact(&mut name)?;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        ids.push(blocks.lookup(&name).ok_or(SaveError::UnknownBlock { name })?);
+    }
+
+    let mut chunks = Vec::new();
+    let mut bytes = vec![0; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE];
+    for _ in 0..read_u32(&mut file)? {
+        let position = [read_u32(&mut file)? as i32, read_u32(&mut file)? as i32, read_u32(&mut file)? as i32];
+        file.read_exact(&mut bytes)?;
+        let mut voxels: VoxelMap = [[[0; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        for (voxel, &byte) in voxels.iter_mut().flatten().flatten().zip(bytes.iter()) {
+            *voxel = *ids.get(byte as usize).ok_or(SaveError::NotASave)?;
+        }
+        chunks.push((position, voxels));
+    }
+    Ok((World::from_voxels(chunks, seed, blocks), seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::block::{self, tests::load};
+
+    const STONE: &str = r#"(name: "stone", textures: (all: "stone"), solid: true, hardness: 1.5)"#;
+    const SAND: &str = r#"(name: "sand", textures: (all: "sand"), solid: true, hardness: 0.5)"#;
+    const DIRT: &str = r#"(name: "dirt", textures: (all: "dirt"), solid: true, hardness: 0.5)"#;
+    const WATER: &str = r#"(name: "water", textures: (all: "water"), solid: false, hardness: 100.0,
+        fluid: (flow_distance: 7, tick_interval: 5))"#;
+
+    fn temp_path() -> std::path::PathBuf {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        std::env::temp_dir().join(format!("ferrous_save_{}_{}", std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed)))
+    }
+
+    fn chunk(blocks: &BlockRegistry, seed: usize) -> VoxelMap {
+        let ids = ["air", "stone", "sand", "water:3"].map(|name| blocks.lookup(name).unwrap());
+        let mut voxels: VoxelMap = [[[block::AIR; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        for (i, voxel) in voxels.iter_mut().flatten().flatten().enumerate() {
+            *voxel = ids[(i * 7 + seed) % 11 % ids.len()];
+        }
+        voxels
+    }
+
+    #[test]
+    fn round_trip_with_new_ids() {
+        let before = load(&[("a.ron", STONE), ("b.ron", SAND), ("c.ron", WATER)]).unwrap();
+        let world = World::from_voxels(vec![([0, 0, 0], chunk(&before, 0)), ([-1, 2, 3], chunk(&before, 5))], 42, &before);
+        let path = temp_path();
+        save_world(&path, &world, 42, &before).unwrap();
+
+        // every block moves to a new id
+        let after = load(&[("a.ron", DIRT), ("b.ron", WATER), ("c.ron", SAND), ("d.ron", STONE)]).unwrap();
+        let (loaded, seed) = load_world(&path, &after).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(seed, 42);
+        assert_eq!(loaded.chunks.len(), 2);
+        for (position, seed) in [([0, 0, 0], 0), ([-1, 2, 3], 5)] {
+            assert!(loaded.chunks[&position].mesh.voxels() == &chunk(&after, seed));
+        }
+    }
+
+    fn header(version: u32, names: &[&str]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(version.to_le_bytes());
+        bytes.extend(7u32.to_le_bytes());
+        bytes.extend((names.len() as u32).to_le_bytes());
+        for name in names {
+            bytes.extend((name.len() as u32).to_le_bytes());
+            bytes.extend(name.as_bytes());
+        }
+        bytes
+    }
+
+    fn load_bytes(bytes: &[u8]) -> Result<(World, u32), SaveError> {
+        let blocks = load(&[("stone.ron", STONE)]).unwrap();
+        let path = temp_path();
+        std::fs::write(&path, bytes).unwrap();
+        let result = load_world(&path, &blocks);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(matches!(load_bytes(b"PNG\0 not a world"), Err(SaveError::NotASave)));
+        assert!(matches!(load_bytes(b"FR"), Err(SaveError::Io(_))));
+        assert!(matches!(load_bytes(&header(SAVE_VERSION + 1, &[])), Err(SaveError::Version { found }) if found == SAVE_VERSION + 1));
+    }
+
+    #[test]
+    fn rejects_unknown_blocks() {
+        let result = load_bytes(&header(SAVE_VERSION, &["air", "stone", "marble"]));
+        assert!(matches!(result, Err(SaveError::UnknownBlock { name }) if name == "marble"));
+    }
+
+    #[test]
+    fn rejects_voxels_out_of_range() {
+        let mut bytes = header(SAVE_VERSION, &["air", "stone"]);
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend([0u8; 12]);
+        let mut voxels = vec![1u8; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE];
+        voxels[100] = 2;
+        bytes.extend(voxels);
+        assert!(matches!(load_bytes(&bytes), Err(SaveError::NotASave)));
+    }
+}
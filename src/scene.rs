@@ -0,0 +1,95 @@
+use std::ffi::c_void;
+
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    texture::{DepthFormat, DepthTexture2d, MipmapsOption, SrgbTexture2d},
+    uniforms::MagnifySamplerFilter,
+    Display, Frame, Surface, Version, Api,
+};
+
+// from glClipControl, OpenGL 4.5 or ARB_clip_control
+const GL_LOWER_LEFT: u32 = 0x8CA1;
+const GL_NEGATIVE_ONE_TO_ONE: u32 = 0x935E;
+const GL_ZERO_TO_ONE: u32 = 0x935F;
+
+type ClipControl = extern "system" fn(origin: u32, depth: u32);
+
+/// The world is drawn into this rather than the window, so it can have a
+/// float depth buffer, and copied to the window at the end of the frame.
+///
+/// Reverse-z only gains precision when depth goes into the buffer as it
+/// comes out of the projection. With OpenGL's usual -1 to 1 depth range
+/// it's halved and moved to 0.5 to 1, where a float is no more precise
+/// than the window's 24 bit depth buffer, so while drawing with reverse-z
+/// the range is switched to 0 to 1 with glClipControl. glium doesn't
+/// wrap it, so it's loaded from the context directly.
+pub struct SceneTarget {
+    color: SrgbTexture2d,
+    depth: DepthTexture2d,
+    clip_control: Option<ClipControl>,
+}
+
+impl SceneTarget {
+    pub fn new(display: &Display) -> Result<Self, Box<dyn std::error::Error>> {
+        let (width, height) = display.get_framebuffer_dimensions();
+        let (color, depth) = create_textures(display, width, height)?;
+        let clip_control = load_clip_control(display);
+        if clip_control.is_none() {
+            println!("glClipControl is unsupported, reverse-z will only use half of the depth buffer");
+        }
+        Ok(Self { color, depth, clip_control })
+    }
+
+    // follows the window's size, called before drawing each frame
+    pub fn resize(&mut self, display: &Display) {
+        let (width, height) = display.get_framebuffer_dimensions();
+        // minimised windows are zero sized, keep the old textures until it's back
+        if width == 0 || height == 0 || (width, height) == self.color.dimensions() {
+            return;
+        }
+        let (color, depth) = create_textures(display, width, height).unwrap();
+        self.color = color;
+        self.depth = depth;
+    }
+
+    pub fn framebuffer(&self, display: &Display) -> SimpleFrameBuffer<'_> {
+        SimpleFrameBuffer::with_depth_buffer(display, &self.color, &self.depth).unwrap()
+    }
+
+    // depth from 0 to 1 for reverse-z, back to -1 to 1 for everything else.
+    // it isn't part of glium's state so it has to be put back after drawing
+    pub fn set_zero_to_one_depth(&self, zero_to_one: bool) {
+        if let Some(clip_control) = self.clip_control {
+            let depth = if zero_to_one { GL_ZERO_TO_ONE } else { GL_NEGATIVE_ONE_TO_ONE };
+            clip_control(GL_LOWER_LEFT, depth);
+        }
+    }
+
+    // copy what was drawn to the window, it's the same size so nothing is filtered
+    pub fn present(&self, display: &Display, frame: &Frame) {
+        self.framebuffer(display).fill(frame, MagnifySamplerFilter::Nearest);
+    }
+}
+
+fn create_textures(
+    display: &Display,
+    width: u32,
+    height: u32,
+) -> Result<(SrgbTexture2d, DepthTexture2d), Box<dyn std::error::Error>> {
+    let color = SrgbTexture2d::empty(display, width, height)?;
+    let depth = DepthTexture2d::empty_with_format(display, DepthFormat::F32, MipmapsOption::NoMipmap, width, height)?;
+    Ok((color, depth))
+}
+
+fn load_clip_control(display: &Display) -> Option<ClipControl> {
+    match *display.get_opengl_version() {
+        Version(Api::Gl, major, minor) if (major, minor) >= (4, 5) => {},
+        _ => return None,
+    }
+    let address = display.gl_window().get_proc_address("glClipControl");
+    if address.is_null() {
+        return None;
+    }
+    // the function is part of OpenGL 4.5, which the context was checked for
+    Some(unsafe { std::mem::transmute::<*const c_void, ClipControl>(address) })
+}
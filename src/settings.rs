@@ -0,0 +1,282 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::LookSettings;
+use crate::controller::FirstPersonSettings;
+use crate::input::Bindings;
+use crate::window::WindowSettings;
+use crate::world::WORLD_RADIUS;
+
+// bumped whenever a setting is renamed or changes meaning, older files are
+// upgraded in `Settings::upgrade`
+pub const SETTINGS_VERSION: u32 = 1;
+// default settings file, written with the defaults if it doesn't exist
+pub const SETTINGS: &str = "settings.toml";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    // vertical field of view in degrees
+    pub fov: f32,
+    // chunks drawn around the camera, the fog ends here
+    pub render_distance: u32,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            // camera::FOV, which in degrees picks up rounding errors
+            fov: 60.0,
+            render_distance: WORLD_RADIUS as u32,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlSettings {
+    // degrees turned per mouse count
+    pub mouse_sensitivity: f32,
+    pub invert_y: bool,
+    pub look_smoothing: f32,
+    // degrees per second turned with a gamepad stick
+    pub stick_speed: f32,
+    // blocks per second flown, and how much faster with MoveFaster held
+    pub fly_speed: f32,
+    pub fast_scale: f32,
+    // replaces the buttons bindings.ron gives these actions, written like
+    // MoveForward = [{ Key = "Up" }, { Pad = "DPadUp" }]
+    pub bindings: Bindings,
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        let look = LookSettings::default();
+        let movement = FirstPersonSettings::default();
+        Self {
+            mouse_sensitivity: look.sensitivity,
+            invert_y: look.invert_y,
+            look_smoothing: look.smoothing,
+            stick_speed: look.stick_speed,
+            fly_speed: movement.velocity,
+            fast_scale: movement.fast_scale,
+            bindings: Bindings::default(),
+        }
+    }
+}
+
+impl ControlSettings {
+    pub fn look(&self) -> LookSettings {
+        LookSettings {
+            sensitivity: self.mouse_sensitivity,
+            invert_y: self.invert_y,
+            smoothing: self.look_smoothing,
+            stick_speed: self.stick_speed,
+        }
+    }
+
+    pub fn movement(&self) -> FirstPersonSettings {
+        FirstPersonSettings {
+            velocity: self.fly_speed,
+            fast_scale: self.fast_scale,
+            ..FirstPersonSettings::default()
+        }
+    }
+}
+
+/// Everything a player can change without touching the assets, kept in a
+/// TOML file. Missing settings take their defaults.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // `SETTINGS_VERSION` when the file was written, 0 if it's older than that
+    pub version: u32,
+    pub window: WindowSettings,
+    pub graphics: GraphicsSettings,
+    pub controls: ControlSettings,
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    // written by a newer build that this one can't read
+    Version { found: u32 },
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SettingsError::Io(e) => write!(f, "{}", e),
+            SettingsError::Parse(e) => write!(f, "{}", e),
+            SettingsError::Version { found } => {
+                write!(f, "settings version {} is newer than the supported version {}", found, SETTINGS_VERSION)
+            },
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl From<std::io::Error> for SettingsError {
+    fn from(error: std::io::Error) -> Self {
+        SettingsError::Io(error)
+    }
+}
+
+// replaces a setting outside of `range` with its default, saying why
+macro_rules! check {
+    ($problems:ident, $value:expr, $default:expr, $range:expr) => {
+        if !$range.contains(&$value) {
+            $problems.push(format!(
+                "{} is {} but must be within {:?}, using {}",
+                stringify!($value).trim_start_matches("self."), $value, $range, $default
+            ));
+            $value = $default;
+        }
+    };
+}
+
+impl Settings {
+    pub fn from_toml(source: &str) -> Result<Self, SettingsError> {
+        let settings: Settings = toml::from_str(source).map_err(SettingsError::Parse)?;
+        if settings.version > SETTINGS_VERSION {
+            return Err(SettingsError::Version { found: settings.version });
+        }
+        Ok(settings)
+    }
+
+    // brings settings from an older file up to date, returning whether
+    // anything changed
+    fn upgrade(&mut self) -> bool {
+        if self.version == SETTINGS_VERSION {
+            return false;
+        }
+        // nothing has been renamed since the first version, upgrades go here
+        self.version = SETTINGS_VERSION;
+        true
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap()
+    }
+
+    /// Reads the settings at `path`, writing the defaults there first if
+    /// there's no file yet so they can be edited.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SettingsError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            let settings = Settings { version: SETTINGS_VERSION, ..Default::default() };
+            settings.save(path)?;
+            return Ok(settings);
+        }
+        let mut settings = Self::from_toml(&std::fs::read_to_string(path)?)?;
+        if settings.upgrade() {
+            settings.save(path)?;
+        }
+        Ok(settings)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SettingsError> {
+        Ok(std::fs::write(path, self.to_toml())?)
+    }
+
+    /// Puts settings that are out of range back to their defaults, returning
+    /// what was wrong with each of them.
+    pub fn validate(&mut self) -> Vec<String> {
+        let window = WindowSettings::default();
+        let graphics = GraphicsSettings::default();
+        let controls = ControlSettings::default();
+        let mut problems = Vec::new();
+        check!(problems, self.window.width, window.width, 1..=16384);
+        check!(problems, self.window.height, window.height, 1..=16384);
+        check!(problems, self.graphics.fov, graphics.fov, 10.0..=170.0);
+        check!(problems, self.graphics.render_distance, graphics.render_distance, 1..=64);
+        check!(problems, self.controls.mouse_sensitivity, controls.mouse_sensitivity, 0.001..=10.0);
+        check!(problems, self.controls.look_smoothing, controls.look_smoothing, 0.0..=0.99);
+        check!(problems, self.controls.stick_speed, controls.stick_speed, 1.0..=1000.0);
+        check!(problems, self.controls.fly_speed, controls.fly_speed, 0.01..=1000.0);
+        check!(problems, self.controls.fast_scale, controls.fast_scale, 1.0..=100.0);
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Action, Button};
+    use glium::glutin::event::VirtualKeyCode;
+
+    #[test]
+    fn out_of_range_values() {
+        let mut settings = Settings::from_toml("
+            [window]
+            width = 0
+            [graphics]
+            fov = 500.0
+            render_distance = 4
+            [controls]
+            look_smoothing = -1.0
+        ").unwrap();
+        let problems = settings.validate();
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems[0].starts_with("window.width is 0"));
+        assert!(problems[1].starts_with("graphics.fov is 500"));
+        assert!(problems[2].starts_with("controls.look_smoothing is -1"));
+        assert_eq!(settings.window.width, WindowSettings::default().width);
+        assert_eq!(settings.graphics.fov, 60.0);
+        assert_eq!(settings.controls.look_smoothing, ControlSettings::default().look_smoothing);
+        // settings in range are kept
+        assert_eq!(settings.graphics.render_distance, 4);
+        assert!(settings.validate().is_empty());
+    }
+
+    #[test]
+    fn partial_file() {
+        let settings = Settings::from_toml("version = 1\n[controls]\ninvert_y = true\n").unwrap();
+        assert!(settings.controls.invert_y);
+        assert_eq!(settings.controls.mouse_sensitivity, ControlSettings::default().mouse_sensitivity);
+        assert_eq!(settings.graphics.render_distance, GraphicsSettings::default().render_distance);
+        assert_eq!(settings.window.width, WindowSettings::default().width);
+        assert_eq!(Settings::from_toml("").unwrap().to_toml(), Settings::default().to_toml());
+    }
+
+    #[test]
+    fn upgrades_unversioned() {
+        let path = std::env::temp_dir().join(format!("ferrous_settings_{}.toml", std::process::id()));
+        std::fs::write(&path, "[graphics]\nfov = 90.0\n").unwrap();
+        let settings = Settings::load(&path).unwrap();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.graphics.fov, 90.0);
+        // and the upgraded file is written back
+        let written = Settings::from_toml(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written.version, SETTINGS_VERSION);
+        assert_eq!(written.graphics.fov, 90.0);
+    }
+
+    #[test]
+    fn newer_version() {
+        let source = format!("version = {}\n", SETTINGS_VERSION + 1);
+        assert!(matches!(Settings::from_toml(&source), Err(SettingsError::Version { found }) if found == SETTINGS_VERSION + 1));
+        assert!(matches!(Settings::from_toml("version = \"one\""), Err(SettingsError::Parse(_))));
+    }
+
+    #[test]
+    fn bindings_merge() {
+        let settings = Settings::from_toml("
+            [controls.bindings]
+            MoveForward = [{ Key = \"Up\" }, { Mouse = \"Middle\" }]
+        ").unwrap();
+        let shipped = Bindings::from_ron(&std::fs::read_to_string("assets/bindings.ron").unwrap()).unwrap();
+        let mut bindings = shipped.clone();
+        bindings.merge(&settings.controls.bindings);
+        assert_eq!(bindings.buttons(Action::MoveForward), [
+            Button::Key(VirtualKeyCode::Up),
+            Button::Mouse(glium::glutin::event::MouseButton::Middle),
+        ]);
+        assert_eq!(bindings.buttons(Action::MoveBackward), shipped.buttons(Action::MoveBackward));
+        assert!(!bindings.buttons(Action::Quit).is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use glium::{Display, Program};
+
+use crate::assets::AssetManager;
+use crate::preprocessor::Preprocessor;
+
+// how often the shader files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A shader program that is rebuilt whenever one of its source files,
+/// including the files they `#include`, changes on disk. When a rebuild fails
+/// the previous program keeps being used.
+pub struct ShaderProgram {
+    program: Program,
+    vertex_path: String,
+    fragment_path: String,
+    preprocessor: Preprocessor,
+    // every file the program was built from and when it was last modified
+    files: Vec<(String, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+impl ShaderProgram {
+    pub fn load(
+        display: &Display,
+        assets: &mut AssetManager,
+        preprocessor: &Preprocessor,
+        vertex_path: &str,
+        fragment_path: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (program, files) = compile(display, assets, preprocessor, vertex_path, fragment_path)?;
+        Ok(Self {
+            program,
+            vertex_path: vertex_path.to_string(),
+            fragment_path: fragment_path.to_string(),
+            preprocessor: preprocessor.clone(),
+            files: modified_times(assets, files),
+            last_poll: Instant::now(),
+        })
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    // polls the shader files and recompiles the program if any have changed
+    pub fn reload_if_changed(&mut self, display: &Display, assets: &mut AssetManager) {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = Instant::now();
+
+        if self.files.iter().all(|(path, modified)| assets.modified(path) == *modified) {
+            return;
+        }
+
+        for (path, _) in self.files.iter() {
+            assets.invalidate(path);
+        }
+        let result = compile(display, assets, &self.preprocessor, &self.vertex_path, &self.fragment_path);
+        match result {
+            Ok((program, files)) => {
+                self.program = program;
+                self.files = modified_times(assets, files);
+                println!("Shaders Reloaded");
+            },
+            Err(e) => {
+                // remember the broken versions so the error is only printed once
+                let files = self.files.iter().map(|(path, _)| path.clone()).collect();
+                self.files = modified_times(assets, files);
+                eprintln!("Failed to reload shaders, keeping the previous program: {}", e);
+            },
+        }
+    }
+}
+
+fn modified_times(assets: &AssetManager, files: Vec<String>) -> Vec<(String, Option<SystemTime>)> {
+    files.into_iter().map(|path| {
+        let modified = assets.modified(&path);
+        (path, modified)
+    }).collect()
+}
+
+fn compile(
+    display: &Display,
+    assets: &mut AssetManager,
+    preprocessor: &Preprocessor,
+    vertex_path: &str,
+    fragment_path: &str,
+) -> Result<(Program, Vec<String>), Box<dyn std::error::Error>> {
+    let mut load = |path: &str| assets.load_string(path);
+    let vertex = preprocessor.process(vertex_path, &mut load)?;
+    let fragment = preprocessor.process(fragment_path, &mut load)?;
+    let program = Program::from_source(display, &vertex.source, &fragment.source, None)?;
+
+    let mut files = vertex.files;
+    files.extend(fragment.files);
+    files.sort();
+    files.dedup();
+    Ok((program, files))
+}
@@ -0,0 +1,245 @@
+use glium::{
+    framebuffer::SimpleFrameBuffer,
+    texture::DepthTexture2dArray,
+    uniforms::{DepthTextureComparison, MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction},
+    Display,
+    Program,
+};
+use vecmath::{col_mat4_mul, vec3_add, vec3_cross, vec3_dot, vec3_len, vec3_normalized, vec3_scale, vec3_sub, Matrix4, Vector3};
+
+use crate::assets::AssetManager;
+use crate::preprocessor::Preprocessor;
+use crate::shader::ShaderProgram;
+
+// number of shadow maps, each covering a further slice of the view frustum
+pub const CASCADES: usize = 3;
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+// shadows are only drawn this far from the camera
+pub const SHADOW_DISTANCE: f32 = 160.0;
+// blend between even (0) and logarithmic (1) cascade splits
+const SPLIT_LAMBDA: f32 = 0.75;
+// extra depth behind each cascade so blocks outside of the view frustum
+// still cast shadows into it
+const CASTER_MARGIN: f32 = 128.0;
+
+/// The part of the camera the cascades are fitted to.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    pub position: Vector3<f32>,
+    pub forward: Vector3<f32>,
+    // vertical field of view in radians
+    pub fov: f32,
+    // width over height
+    pub aspect: f32,
+}
+
+impl Frustum {
+    // the eight corners of the slice of the frustum between two view depths
+    pub fn corners(&self, near: f32, far: f32) -> [Vector3<f32>; 8] {
+        let forward = vec3_normalized(self.forward);
+        let mut right = vec3_cross([0.0, 1.0, 0.0], forward);
+        if vec3_len(right) < 1e-4 {
+            // looking straight up or down
+            right = [1.0, 0.0, 0.0];
+        }
+        let right = vec3_normalized(right);
+        let up = vec3_cross(forward, right);
+
+        let tan = (self.fov / 2.0).tan();
+        let mut corners = [[0.0; 3]; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let depth = if i < 4 { near } else { far };
+            let (x, y) = (if i & 1 == 0 { -1.0 } else { 1.0 }, if i & 2 == 0 { -1.0 } else { 1.0 });
+            let center = vec3_add(self.position, vec3_scale(forward, depth));
+            let offset = vec3_add(
+                vec3_scale(right, x * depth * tan * self.aspect),
+                vec3_scale(up, y * depth * tan),
+            );
+            *corner = vec3_add(center, offset);
+        }
+        corners
+    }
+}
+
+/// View depths at which each cascade ends, mixing evenly spaced and
+/// logarithmic splits so nearby cascades get more of the resolution.
+pub fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (1..=count)
+        .map(|i| {
+            let t = i as f32 / count as f32;
+            let logarithmic = near * (far / near).powf(t);
+            let uniform = near + (far - near) * t;
+            lambda * logarithmic + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// Orthographic projection from the sun covering every corner, as a single
+/// matrix from world space to shadow map clip space. The box is fitted to a
+/// sphere around the corners and snapped to whole texels so shadow edges
+/// don't shimmer as the camera turns and moves. Also returns the size of a
+/// shadow map texel in blocks.
+pub fn light_matrix(corners: &[Vector3<f32>], light_direction: Vector3<f32>, map_size: u32) -> (Matrix4<f32>, f32) {
+    let center = vec3_scale(corners.iter().fold([0.0; 3], |sum, &c| vec3_add(sum, c)), 1.0 / corners.len() as f32);
+    let radius = corners
+        .iter()
+        .map(|&c| vec3_len(vec3_sub(c, center)))
+        .fold(0.0, f32::max)
+        .ceil();
+
+    // the light looks along `forward`, from the sun towards the world
+    let forward = vec3_normalized(vec3_scale(light_direction, -1.0));
+    let mut right = vec3_cross([0.0, 1.0, 0.0], forward);
+    if vec3_len(right) < 1e-4 {
+        right = [1.0, 0.0, 0.0];
+    }
+    let right = vec3_normalized(right);
+    let up = vec3_cross(forward, right);
+    let rotation = [
+        [right[0], up[0], forward[0], 0.0],
+        [right[1], up[1], forward[1], 0.0],
+        [right[2], up[2], forward[2], 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    let texel = 2.0 * radius / map_size as f32;
+    let x = (vec3_dot(center, right) / texel).floor() * texel;
+    let y = (vec3_dot(center, up) / texel).floor() * texel;
+    let z = vec3_dot(center, forward);
+    let (near, far) = (z - radius - CASTER_MARGIN, z + radius);
+    let projection = [
+        [1.0 / radius, 0.0, 0.0, 0.0],
+        [0.0, 1.0 / radius, 0.0, 0.0],
+        [0.0, 0.0, 2.0 / (far - near), 0.0],
+        [-x / radius, -y / radius, -(far + near) / (far - near), 1.0],
+    ];
+    (col_mat4_mul(projection, rotation), texel)
+}
+
+/// One shadow map and the slice of the view it covers.
+#[derive(Clone, Copy, Debug)]
+pub struct Cascade {
+    // view depth the cascade ends at
+    pub far: f32,
+    pub matrix: Matrix4<f32>,
+    // size of a shadow map texel in blocks
+    pub texel: f32,
+}
+
+pub fn fit_cascades(frustum: &Frustum, near: f32, light_direction: Vector3<f32>) -> [Cascade; CASCADES] {
+    let splits = cascade_splits(near, SHADOW_DISTANCE, CASCADES, SPLIT_LAMBDA);
+    let mut start = near;
+    let mut cascades = [Cascade { far: 0.0, matrix: [[0.0; 4]; 4], texel: 0.0 }; CASCADES];
+    for (cascade, &far) in cascades.iter_mut().zip(splits.iter()) {
+        let (matrix, texel) = light_matrix(&frustum.corners(start, far), light_direction, SHADOW_MAP_SIZE);
+        *cascade = Cascade { far, matrix, texel };
+        start = far;
+    }
+    cascades
+}
+
+/// Depth textures the world is rendered into from the sun, one layer per
+/// cascade, and the program that renders them.
+pub struct ShadowMaps {
+    depth: DepthTexture2dArray,
+    program: ShaderProgram,
+}
+
+impl ShadowMaps {
+    pub fn new(
+        display: &Display,
+        assets: &mut AssetManager,
+        preprocessor: &Preprocessor,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let depth = DepthTexture2dArray::empty(display, SHADOW_MAP_SIZE, SHADOW_MAP_SIZE, CASCADES as u32)?;
+        let program = ShaderProgram::load(display, assets, preprocessor, "shaders/shadow_vertex.glsl", "shaders/shadow_fragment.glsl")?;
+        Ok(Self { depth, program })
+    }
+
+    pub fn reload_if_changed(&mut self, display: &Display, assets: &mut AssetManager) {
+        self.program.reload_if_changed(display, assets);
+    }
+
+    pub fn program(&self) -> &Program {
+        self.program.program()
+    }
+
+    pub fn framebuffer(&self, display: &Display, cascade: usize) -> SimpleFrameBuffer<'_> {
+        let layer = self.depth.layer(cascade as u32).unwrap();
+        SimpleFrameBuffer::depth_only(display, layer.main_level()).unwrap()
+    }
+
+    // sampled with depth comparison, for sampler2DArrayShadow
+    pub fn sampled(&self) -> Sampler<'_, DepthTexture2dArray> {
+        self.depth
+            .sampled()
+            .magnify_filter(MagnifySamplerFilter::Linear)
+            .minify_filter(MinifySamplerFilter::Linear)
+            .wrap_function(SamplerWrapFunction::Clamp)
+            .depth_texture_comparison(Some(DepthTextureComparison::LessOrEqual))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vecmath::col_mat4_transform;
+
+    fn frustum(position: Vector3<f32>) -> Frustum {
+        Frustum {
+            position,
+            forward: [0.6, -0.3, 0.75],
+            fov: std::f32::consts::PI / 3.0,
+            aspect: 16.0 / 9.0,
+        }
+    }
+
+    const LIGHT: Vector3<f32> = [0.4, 0.8, 0.3];
+
+    #[test]
+    fn splits_increase_to_shadow_distance() {
+        for lambda in [0.0, 0.5, SPLIT_LAMBDA, 1.0] {
+            let splits = cascade_splits(0.1, SHADOW_DISTANCE, CASCADES, lambda);
+            assert_eq!(splits.len(), CASCADES);
+            assert!(splits[0] > 0.1);
+            for pair in splits.windows(2) {
+                assert!(pair[0] < pair[1], "{:?}", splits);
+            }
+            assert!((splits[CASCADES - 1] - SHADOW_DISTANCE).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn corners_inside_cascades() {
+        let frustum = frustum([10.5, 70.0, -3.25]);
+        let near = 0.1;
+        let cascades = fit_cascades(&frustum, near, LIGHT);
+        let mut start = near;
+        for cascade in cascades.iter() {
+            for corner in frustum.corners(start, cascade.far) {
+                let clip = col_mat4_transform(cascade.matrix, [corner[0], corner[1], corner[2], 1.0]);
+                for value in &clip[..3] {
+                    assert!(value.abs() <= 1.0, "{:?} outside of the shadow map", clip);
+                }
+                assert_eq!(clip[3], 1.0);
+            }
+            start = cascade.far;
+        }
+    }
+
+    #[test]
+    fn snapped_to_texels() {
+        // wherever the camera is, a fixed point in the world lands on the
+        // same spot within a texel
+        for position in [[0.0, 64.0, 0.0], [0.3, 64.0, 0.1], [17.77, 65.2, -4.9], [-100.01, 80.0, 33.3]] {
+            let cascades = fit_cascades(&frustum(position), 0.1, LIGHT);
+            for cascade in cascades.iter() {
+                let clip = col_mat4_transform(cascade.matrix, [0.0, 0.0, 0.0, 1.0]);
+                for value in &clip[..2] {
+                    let texels = (value + 1.0) / 2.0 * SHADOW_MAP_SIZE as f32;
+                    assert!((texels - texels.round()).abs() < 0.01, "{} is not on a texel edge", texels);
+                }
+            }
+        }
+    }
+}
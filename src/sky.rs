@@ -0,0 +1,110 @@
+use glium::{
+    implement_vertex,
+    index::{NoIndices, PrimitiveType},
+    uniform,
+    Display,
+    DrawParameters,
+    Surface,
+    VertexBuffer,
+};
+
+use crate::assets::AssetManager;
+use crate::preprocessor::Preprocessor;
+use crate::shader::ShaderProgram;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FogMode {
+    // fades in between `start` and `end`
+    Linear,
+    // thickens with distance at a rate set by `density`
+    Exponential,
+}
+
+impl FogMode {
+    pub fn toggled(self) -> Self {
+        match self {
+            FogMode::Linear => FogMode::Exponential,
+            FogMode::Exponential => FogMode::Linear,
+        }
+    }
+}
+
+/// Fog blending the world into the sky colour towards the render distance.
+#[derive(Clone, Copy, Debug)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub start: f32,
+    pub end: f32,
+    pub density: f32,
+}
+
+impl Fog {
+    // fog that hides everything past `render_distance` blocks
+    pub fn new(mode: FogMode, render_distance: f32) -> Self {
+        Self {
+            mode,
+            start: render_distance * 0.6,
+            end: render_distance,
+            // 95% fog at the render distance
+            density: 3.0 / render_distance,
+        }
+    }
+}
+
+/// Colours of the sky gradient, also used as the fog colour.
+#[derive(Clone, Copy, Debug)]
+pub struct SkyColors {
+    pub horizon: [f32; 3],
+    pub zenith: [f32; 3],
+}
+
+#[derive(Copy, Clone)]
+struct SkyVertex {
+    position: [f32; 2],
+}
+
+implement_vertex!(SkyVertex, position);
+
+/// Draws the sky gradient behind the world as a single triangle covering
+/// the screen.
+pub struct Sky {
+    vertices: VertexBuffer<SkyVertex>,
+    program: ShaderProgram,
+}
+
+impl Sky {
+    pub fn new(
+        display: &Display,
+        assets: &mut AssetManager,
+        preprocessor: &Preprocessor,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let vertices = VertexBuffer::new(display, &[
+            SkyVertex { position: [-1.0, -1.0] },
+            SkyVertex { position: [3.0, -1.0] },
+            SkyVertex { position: [-1.0, 3.0] },
+        ])?;
+        let program = ShaderProgram::load(display, assets, preprocessor, "shaders/sky_vertex.glsl", "shaders/sky_fragment.glsl")?;
+        Ok(Self { vertices, program })
+    }
+
+    pub fn reload_if_changed(&mut self, display: &Display, assets: &mut AssetManager) {
+        self.program.reload_if_changed(display, assets);
+    }
+
+    // draw before anything else, it neither tests nor writes depth
+    pub fn draw<S: Surface>(
+        &self,
+        target: &mut S,
+        view: [[f32; 4]; 4],
+        perspective: [[f32; 4]; 4],
+        colors: &SkyColors,
+        sun_direction: [f32; 3],
+        sun_color: [f32; 3],
+    ) {
+        target.draw(&self.vertices, NoIndices(PrimitiveType::TrianglesList), self.program.program(),
+            &uniform! { view: view, perspective: perspective,
+                sky_horizon: colors.horizon, sky_zenith: colors.zenith,
+                sun_direction: sun_direction, sun_color: sun_color },
+            &DrawParameters::default()).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use std::f32::consts::PI;
+use std::fmt;
+
+use glium::{
+    draw_parameters,
+    framebuffer::SimpleFrameBuffer,
+    implement_vertex,
+    index::PrimitiveType,
+    texture::{CubeLayer, RawImage2d, SrgbCubemap, SrgbTexture2d},
+    uniform,
+    uniforms::MagnifySamplerFilter,
+    BlitTarget,
+    Display,
+    DrawParameters,
+    IndexBuffer,
+    Surface,
+    VertexBuffer,
+};
+
+use crate::assets::AssetManager;
+use crate::preprocessor::Preprocessor;
+use crate::shader::ShaderProgram;
+
+// one image per cubemap face, in `CubeLayer` order
+const FACES: [(&str, CubeLayer); 6] = [
+    ("px", CubeLayer::PositiveX),
+    ("nx", CubeLayer::NegativeX),
+    ("py", CubeLayer::PositiveY),
+    ("ny", CubeLayer::NegativeY),
+    ("pz", CubeLayer::PositiveZ),
+    ("nz", CubeLayer::NegativeZ),
+];
+
+// a single equirectangular image, used when the six faces aren't there
+const EQUIRECTANGULAR: &str = "textures/skybox.png";
+
+fn face_path(face: &str) -> String {
+    format!("textures/skybox/{}.png", face)
+}
+
+#[derive(Debug)]
+pub enum SkyboxError {
+    FaceSize { path: String, size: (u32, u32), expected: u32 },
+}
+
+impl fmt::Display for SkyboxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkyboxError::FaceSize { path, size, expected } => write!(
+                f,
+                "skybox face {} is {}x{} but every face must be {}x{}",
+                path, size.0, size.1, expected, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SkyboxError {}
+
+#[derive(Copy, Clone)]
+struct SkyboxVertex {
+    position: [f32; 3],
+}
+
+implement_vertex!(SkyboxVertex, position);
+
+/// A cubemap drawn around the camera in place of the sky gradient.
+pub struct Skybox {
+    cubemap: SrgbCubemap,
+    vertices: VertexBuffer<SkyboxVertex>,
+    indices: IndexBuffer<u16>,
+    program: ShaderProgram,
+}
+
+impl Skybox {
+    /// Loads `textures/skybox/{px,nx,py,ny,pz,nz}.png`, or failing that an
+    /// equirectangular `textures/skybox.png`. `None` when neither exists.
+    pub fn load(
+        display: &Display,
+        assets: &mut AssetManager,
+        preprocessor: &Preprocessor,
+    ) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let faces = if FACES.iter().all(|(face, _)| assets.exists(&face_path(face))) {
+            load_faces(assets)?
+        } else if assets.exists(EQUIRECTANGULAR) {
+            let image = assets.load_image(EQUIRECTANGULAR)?;
+            equirectangular_to_faces(&image)
+        } else {
+            return Ok(None);
+        };
+
+        let size = faces[0].width();
+        let cubemap = SrgbCubemap::empty(display, size)?;
+        for (image, (_, layer)) in faces.iter().zip(FACES) {
+            // rows are uploaded top first, which is how cubemap faces are laid out
+            let raw = RawImage2d::from_raw_rgba(image.as_raw().clone(), (size, size));
+            let texture = SrgbTexture2d::new(display, raw)?;
+            let source = SimpleFrameBuffer::new(display, &texture)?;
+            let target = SimpleFrameBuffer::new(display, cubemap.main_level().image(layer))?;
+            let rect = BlitTarget { left: 0, bottom: 0, width: size as i32, height: size as i32 };
+            source.blit_whole_color_to(&target, &rect, MagnifySamplerFilter::Linear);
+        }
+
+        // the corners of a unit cube around the camera
+        let corners: Vec<SkyboxVertex> = (0..8)
+            .map(|i| SkyboxVertex {
+                position: [(i & 1) as f32 * 2.0 - 1.0, ((i >> 1) & 1) as f32 * 2.0 - 1.0, (i >> 2) as f32 * 2.0 - 1.0],
+            })
+            .collect();
+        let indices: [u16; 36] = [
+            1, 3, 7, 1, 7, 5, // +x
+            0, 4, 6, 0, 6, 2, // -x
+            2, 6, 7, 2, 7, 3, // +y
+            0, 1, 5, 0, 5, 4, // -y
+            4, 5, 7, 4, 7, 6, // +z
+            0, 2, 3, 0, 3, 1, // -z
+        ];
+        let vertices = VertexBuffer::new(display, &corners)?;
+        let indices = IndexBuffer::new(display, PrimitiveType::TrianglesList, &indices)?;
+        let program = ShaderProgram::load(display, assets, preprocessor, "shaders/skybox_vertex.glsl", "shaders/skybox_fragment.glsl")?;
+        Ok(Some(Self { cubemap, vertices, indices, program }))
+    }
+
+    pub fn reload_if_changed(&mut self, display: &Display, assets: &mut AssetManager) {
+        self.program.reload_if_changed(display, assets);
+    }
+
+    // draw after the opaque world, it only fills in pixels nothing was drawn to
+    pub fn draw<S: Surface>(&self, target: &mut S, rotation: [[f32; 4]; 4], perspective: [[f32; 4]; 4], tint: [f32; 3], reverse_z: bool) {
+        // the far plane is at depth 0 with reverse-z
+        let (test, far_depth) = if reverse_z {
+            (draw_parameters::DepthTest::IfMoreOrEqual, 0.0f32)
+        } else {
+            (draw_parameters::DepthTest::IfLessOrEqual, 1.0f32)
+        };
+        let params = DrawParameters {
+            depth: glium::Depth {
+                test,
+                write: false,
+                .. Default::default()
+            },
+            .. Default::default()
+        };
+        target.draw(&self.vertices, &self.indices, self.program.program(),
+            &uniform! { view: rotation, perspective: perspective, tint: tint, far_depth: far_depth,
+                skybox: self.cubemap.sampled().magnify_filter(MagnifySamplerFilter::Linear) },
+            &params).unwrap();
+    }
+}
+
+fn load_faces(assets: &mut AssetManager) -> Result<Vec<image::RgbaImage>, Box<dyn std::error::Error>> {
+    let mut faces = Vec::new();
+    let mut expected = None;
+    for (face, _) in FACES {
+        let path = face_path(face);
+        let image = assets.load_image(&path)?;
+        let expected = *expected.get_or_insert(image.width());
+        if image.width() != expected || image.height() != expected {
+            return Err(SkyboxError::FaceSize { path, size: image.dimensions(), expected }.into());
+        }
+        faces.push(image);
+    }
+    Ok(faces)
+}
+
+// direction through a point on a cubemap face, with `u` and `v` in -1..1
+// and `v` pointing down the face as in the OpenGL cubemap layout
+fn face_direction(layer: CubeLayer, u: f32, v: f32) -> [f32; 3] {
+    match layer {
+        CubeLayer::PositiveX => [1.0, -v, -u],
+        CubeLayer::NegativeX => [-1.0, -v, u],
+        CubeLayer::PositiveY => [u, 1.0, v],
+        CubeLayer::NegativeY => [u, -1.0, -v],
+        CubeLayer::PositiveZ => [u, -v, 1.0],
+        CubeLayer::NegativeZ => [-u, -v, -1.0],
+    }
+}
+
+/// Resamples an equirectangular panorama, with the top row straight up and
+/// the middle column looking down +z, into six cubemap faces.
+pub fn equirectangular_to_faces(image: &image::RgbaImage) -> Vec<image::RgbaImage> {
+    let (width, height) = image.dimensions();
+    let size = (width / 4).max(1);
+    FACES
+        .iter()
+        .map(|&(_, layer)| {
+            image::RgbaImage::from_fn(size, size, |x, y| {
+                let u = (x as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let v = (y as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                let [dx, dy, dz] = face_direction(layer, u, v);
+                let longitude = dx.atan2(dz);
+                let latitude = (dy / (dx * dx + dy * dy + dz * dz).sqrt()).asin();
+                let px = ((longitude / (2.0 * PI) + 0.5) * width as f32) as u32;
+                let py = ((0.5 - latitude / PI) * height as f32) as u32;
+                *image.get_pixel(px.min(width - 1), py.min(height - 1))
+            })
+        })
+        .collect()
+}
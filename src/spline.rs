@@ -0,0 +1,101 @@
+use vecmath::Vector3;
+
+/// Point on a Catmull-Rom spline between `p1` and `p2`, `t` going from 0 at
+/// `p1` to 1 at `p2`, with `p0` and `p3` shaping the curve on either side.
+pub fn catmull_rom(p0: Vector3<f32>, p1: Vector3<f32>, p2: Vector3<f32>, p3: Vector3<f32>, t: f32) -> Vector3<f32> {
+    let (t2, t3) = (t * t, t * t * t);
+    std::array::from_fn(|i| {
+        0.5 * (2.0 * p1[i]
+            + (p2[i] - p0[i]) * t
+            + (2.0 * p0[i] - 5.0 * p1[i] + 4.0 * p2[i] - p3[i]) * t2
+            + (3.0 * p1[i] - p0[i] - 3.0 * p2[i] + p3[i]) * t3)
+    })
+}
+
+/// Direction the spline is heading at `t`, not normalised.
+pub fn catmull_rom_tangent(p0: Vector3<f32>, p1: Vector3<f32>, p2: Vector3<f32>, p3: Vector3<f32>, t: f32) -> Vector3<f32> {
+    let t2 = t * t;
+    std::array::from_fn(|i| {
+        0.5 * ((p2[i] - p0[i])
+            + 2.0 * (2.0 * p0[i] - 5.0 * p1[i] + 4.0 * p2[i] - p3[i]) * t
+            + 3.0 * (3.0 * p1[i] - p0[i] - 3.0 * p2[i] + p3[i]) * t2)
+    })
+}
+
+// the four points around segment `segment` of a closed loop
+fn loop_segment(points: &[Vector3<f32>], segment: usize) -> [Vector3<f32>; 4] {
+    let n = points.len();
+    [
+        points[(segment + n - 1) % n],
+        points[segment % n],
+        points[(segment + 1) % n],
+        points[(segment + 2) % n],
+    ]
+}
+
+/// Position and heading on a closed loop through `points`, `t` counting
+/// segments so each whole number lands on a point and it wraps around
+/// after the last one.
+pub fn sample_loop(points: &[Vector3<f32>], t: f32) -> (Vector3<f32>, Vector3<f32>) {
+    let t = t.rem_euclid(points.len() as f32);
+    let segment = t.floor() as usize;
+    let [p0, p1, p2, p3] = loop_segment(points, segment);
+    let local = t - segment as f32;
+    (catmull_rom(p0, p1, p2, p3, local), catmull_rom_tangent(p0, p1, p2, p3, local))
+}
+
+/// Like `sample_loop` but along a path that starts at the first point and
+/// ends at the last, `t` being clamped to the path. An empty path stays at
+/// the origin.
+pub fn sample_path(points: &[Vector3<f32>], t: f32) -> Vector3<f32> {
+    let last = match points.len() {
+        0 => return [0.0; 3],
+        n => n - 1,
+    };
+    if last == 0 {
+        return points[0];
+    }
+    let t = t.clamp(0.0, last as f32);
+    let segment = (t.floor() as usize).min(last - 1);
+    // the ends are repeated so the path doesn't curve past them
+    let p0 = points[segment.saturating_sub(1)];
+    let p3 = points[(segment + 2).min(last)];
+    catmull_rom(p0, points[segment], points[segment + 1], p3, t - segment as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTS: [Vector3<f32>; 4] = [[0.0, 0.0, 0.0], [4.0, 1.0, 0.0], [6.0, 3.0, 5.0], [2.0, 0.0, 8.0]];
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn path_through_points() {
+        for (i, &point) in POINTS.iter().enumerate() {
+            assert_close(sample_path(&POINTS, i as f32), point);
+        }
+        // held at the ends
+        assert_close(sample_path(&POINTS, -1.0), POINTS[0]);
+        assert_close(sample_path(&POINTS, 10.0), POINTS[3]);
+        assert_close(sample_path(&POINTS[..1], 0.5), POINTS[0]);
+        assert_close(sample_path(&[], 0.5), [0.0; 3]);
+    }
+
+    #[test]
+    fn loop_through_points() {
+        for (i, &point) in POINTS.iter().enumerate() {
+            assert_close(sample_loop(&POINTS, i as f32).0, point);
+            // and again after going round once
+            assert_close(sample_loop(&POINTS, (i + POINTS.len()) as f32).0, point);
+        }
+        // the tangent at a point runs from the point before it to the one after
+        let (_, tangent) = sample_loop(&POINTS, 1.0);
+        assert_close(tangent, [3.0, 1.5, 2.5]);
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::block::{self, BlockRegistry, BlockTick};
+use crate::fluid;
+use crate::visibility::Face;
+
+// blocks picked at random in each chunk every tick
+pub const RANDOM_TICKS_PER_CHUNK: u32 = 3;
+
+/// Read access to the blocks that ticks run over.
+pub trait VoxelGrid {
+    // `None` outside of the grid, which is treated as solid
+    fn get(&self, position: [i32; 3]) -> Option<i8>;
+}
+
+pub fn offset(position: [i32; 3], face: Face) -> [i32; 3] {
+    let [dx, dy, dz] = face.offset();
+    [position[0] + dx, position[1] + dy, position[2] + dz]
+}
+
+/// Queue of block updates, each due a number of ticks after something next
+/// to it changed.
+#[derive(Default)]
+pub struct TickScheduler {
+    tick: u64,
+    // cells to update, keyed by the tick they are due on
+    scheduled: BTreeMap<u64, BTreeSet<[i32; 3]>>,
+}
+
+impl TickScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // update `position` and the cells around it once `delay` ticks have passed
+    pub fn schedule(&mut self, position: [i32; 3], delay: u32) {
+        let due = self.tick + delay.max(1) as u64;
+        let cells = self.scheduled.entry(due).or_default();
+        cells.insert(position);
+        for face in Face::ALL {
+            cells.insert(offset(position, face));
+        }
+    }
+
+    // moves on to the next tick, returning the cells due on it
+    pub fn advance(&mut self) -> BTreeSet<[i32; 3]> {
+        self.tick += 1;
+        self.scheduled.remove(&self.tick).unwrap_or_default()
+    }
+}
+
+/// Xorshift generator for random ticks, cheap and the same on every run
+/// for a given seed.
+#[derive(Clone, Copy, Debug)]
+pub struct Random(u64);
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero
+        Self(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // a number from 0 up to but not including `n`
+    pub fn below(&mut self, n: u32) -> u32 {
+        (self.next_u64() % n as u64) as u32
+    }
+}
+
+/// What a scheduled update turns a cell into, `None` when it stays as it
+/// is. Every cell due on a tick is updated from the same snapshot of the
+/// grid, so the result only depends on the grid and the order things were
+/// scheduled in.
+pub fn scheduled_update(grid: &impl VoxelGrid, blocks: &BlockRegistry, position: [i32; 3]) -> Option<i8> {
+    let current = grid.get(position)?;
+
+    // falling blocks swap places with the air below them, a block per update
+    if blocks.falls(current) && grid.get(offset(position, Face::Bottom)) == Some(block::AIR) {
+        return Some(block::AIR);
+    }
+    if current == block::AIR {
+        if let Some(above) = grid.get(offset(position, Face::Top)).filter(|&b| blocks.falls(b)) {
+            return Some(above);
+        }
+    }
+
+    fluid::next_block(grid, blocks, position).filter(|&next| next != current)
+}
+
+/// A random tick of the block at `position`, returning the cell it changes
+/// and what that cell becomes.
+pub fn random_update(
+    grid: &impl VoxelGrid,
+    blocks: &BlockRegistry,
+    position: [i32; 3],
+    random: &mut Random,
+) -> Option<([i32; 3], i8)> {
+    let current = grid.get(position)?;
+    match blocks.get(current)?.tick? {
+        BlockTick::Spread { onto } => {
+            if is_covered(grid, blocks, position) {
+                return Some((position, onto));
+            }
+            let target = [
+                position[0] + random.below(3) as i32 - 1,
+                position[1] + random.below(3) as i32 - 1,
+                position[2] + random.below(3) as i32 - 1,
+            ];
+            let spreads = grid.get(target) == Some(onto) && !is_covered(grid, blocks, target);
+            if spreads { Some((target, current)) } else { None }
+        },
+        BlockTick::Grow { into } => Some((position, into)),
+        BlockTick::Fall => None,
+    }
+}
+
+// whether something opaque or a fluid sits on top of a block
+fn is_covered(grid: &impl VoxelGrid, blocks: &BlockRegistry, position: [i32; 3]) -> bool {
+    grid.get(offset(position, Face::Top))
+        .is_some_and(|above| blocks.is_opaque(above) || blocks.fluid(above).is_some())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::assets::AssetManager;
+    use crate::model::ModelRegistry;
+
+    pub(crate) fn registry() -> BlockRegistry {
+        let mut assets = AssetManager::new(crate::ASSETS);
+        let models = ModelRegistry::from_ron(&assets.load_string("models/models.ron").unwrap()).unwrap();
+        BlockRegistry::load(&mut assets, models).unwrap()
+    }
+
+    /// A small cube of blocks, solid outside, with the same scheduling as `World`.
+    pub(crate) struct Grid {
+        size: i32,
+        blocks: HashMap<[i32; 3], i8>,
+        scheduler: TickScheduler,
+    }
+
+    impl Grid {
+        pub(crate) fn new(size: i32) -> Self {
+            Self { size, blocks: HashMap::new(), scheduler: TickScheduler::new() }
+        }
+
+        // without scheduling anything, for building the grid
+        pub(crate) fn fill(&mut self, from: [i32; 3], to: [i32; 3], block: i8) {
+            for x in from[0]..=to[0] {
+                for y in from[1]..=to[1] {
+                    for z in from[2]..=to[2] {
+                        self.blocks.insert([x, y, z], block);
+                    }
+                }
+            }
+        }
+
+        pub(crate) fn set(&mut self, position: [i32; 3], block: i8, registry: &BlockRegistry) {
+            let old = self.get(position).unwrap();
+            self.blocks.insert(position, block);
+            let delay = registry.tick_delay(block).or_else(|| registry.tick_delay(old)).unwrap_or(1);
+            self.scheduler.schedule(position, delay);
+        }
+
+        pub(crate) fn tick(&mut self, registry: &BlockRegistry) {
+            let changes: Vec<([i32; 3], i8)> = self
+                .scheduler
+                .advance()
+                .into_iter()
+                .filter_map(|position| Some((position, scheduled_update(self, registry, position)?)))
+                .collect();
+            for (position, block) in changes {
+                self.set(position, block, registry);
+            }
+        }
+    }
+
+    impl VoxelGrid for Grid {
+        fn get(&self, position: [i32; 3]) -> Option<i8> {
+            if position.iter().any(|&p| p < 0 || p >= self.size) {
+                return None;
+            }
+            Some(self.blocks.get(&position).copied().unwrap_or(block::AIR))
+        }
+    }
+
+    #[test]
+    fn scheduled_in_tick_order() {
+        let mut scheduler = TickScheduler::new();
+        scheduler.schedule([0, 0, 0], 3);
+        scheduler.schedule([10, 0, 0], 1);
+        // a delay of 0 still waits for the next tick
+        scheduler.schedule([20, 0, 0], 0);
+
+        let first = scheduler.advance();
+        assert_eq!(first.len(), 14);
+        assert!(first.contains(&[10, 0, 0]) && first.contains(&[11, 0, 0]));
+        assert!(first.contains(&[20, 0, 0]) && first.contains(&[20, -1, 0]));
+        assert!(!first.contains(&[0, 0, 0]));
+        assert!(scheduler.advance().is_empty());
+        let third = scheduler.advance();
+        assert_eq!(third.len(), 7);
+        assert!(third.contains(&[0, 0, 0]) && third.contains(&[0, 0, 1]));
+        assert!(scheduler.advance().is_empty());
+    }
+
+    #[test]
+    fn scheduled_twice() {
+        let mut scheduler = TickScheduler::new();
+        scheduler.schedule([0, 0, 0], 2);
+        scheduler.schedule([1, 0, 0], 2);
+        scheduler.advance();
+        // each cell is only updated once, however often it was scheduled
+        let cells = scheduler.advance();
+        assert_eq!(cells.len(), 12);
+        let sorted: Vec<[i32; 3]> = cells.iter().copied().collect();
+        let mut expected = sorted.clone();
+        expected.sort();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn sand_falls_a_block_per_update() {
+        let registry = registry();
+        let sand = registry.id("sand").unwrap();
+        let stone = registry.id("stone").unwrap();
+        let mut grid = Grid::new(8);
+        grid.fill([0, 0, 0], [7, 0, 7], stone);
+        grid.set([3, 5, 3], sand, &registry);
+
+        let mut heights = Vec::new();
+        for _ in 0..12 {
+            grid.tick(&registry);
+            let height = (0..8).find(|&y| grid.get([3, y, 3]) == Some(sand));
+            heights.push(height.unwrap());
+        }
+        assert_eq!(heights, [5, 4, 4, 3, 3, 2, 2, 1, 1, 1, 1, 1]);
+        assert_eq!(grid.get([3, 0, 3]), Some(stone));
+    }
+
+    #[test]
+    fn fluids_flow_at_their_own_pace() {
+        let registry = registry();
+        let stone = registry.id("stone").unwrap();
+        for (name, interval) in [("water", 5), ("lava", 30)] {
+            let source = registry.id(name).unwrap();
+            let mut grid = Grid::new(8);
+            grid.fill([0, 0, 0], [7, 0, 7], stone);
+            grid.set([3, 1, 3], source, &registry);
+            for _ in 0..interval - 1 {
+                grid.tick(&registry);
+            }
+            assert_eq!(grid.get([4, 1, 3]), Some(block::AIR));
+            grid.tick(&registry);
+            assert_eq!(registry.fluid(grid.get([4, 1, 3]).unwrap()).map(|f| f.source), Some(source));
+            assert_eq!(grid.get([5, 1, 3]), Some(block::AIR));
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use serde::{Deserialize, Serialize};
+use vecmath::Vector3;
+
+use crate::camera::Camera;
+use crate::controller::{CameraController, ControllerContext};
+use crate::spline;
+
+/// How a keyframe eases into the next one.
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum Easing {
+    #[default]
+    Linear,
+    // starts slowly
+    EaseIn,
+    // slows down into the next keyframe
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Remaps how far through a segment `t` is, from 0 to 1.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// How the camera gets from one keyframe to the next.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Path {
+    // straight lines, for recordings that have a keyframe every step anyway
+    Linear,
+    // a smooth curve through every keyframe, for authored paths
+    CatmullRom,
+}
+
+/// Where the camera is at `time` seconds into a timeline. Angles are in
+/// degrees to keep the files readable.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f32,
+    pub position: Vector3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    #[serde(default)]
+    pub easing: Easing,
+}
+
+#[derive(Debug)]
+pub enum TimelineError {
+    Ron(ron::error::SpannedError),
+    Empty,
+    // keyframe `index` doesn't come after the one before it
+    OutOfOrder { index: usize },
+}
+
+impl std::fmt::Display for TimelineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TimelineError::Ron(e) => write!(f, "{}", e),
+            TimelineError::Empty => write!(f, "the timeline has no keyframes"),
+            TimelineError::OutOfOrder { index } => write!(f, "keyframe {} is not later than the one before it", index),
+        }
+    }
+}
+
+impl std::error::Error for TimelineError {}
+
+/// Keyframed camera movement, either authored by hand or recorded, saved as
+/// RON like the rest of the assets:
+///
+/// ```text
+/// (
+///     path: CatmullRom,
+///     keyframes: [
+///         (time: 0.0, position: (0.0, 80.0, 0.0), yaw: 0.0, pitch: -10.0, easing: EaseIn),
+///         (time: 5.0, position: (40.0, 72.0, 40.0), yaw: 90.0, pitch: 0.0),
+///     ],
+/// )
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Timeline {
+    pub path: Path,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Timeline {
+    pub fn from_ron(source: &str) -> Result<Self, TimelineError> {
+        let mut timeline: Timeline = ron::from_str(source).map_err(TimelineError::Ron)?;
+        if timeline.keyframes.is_empty() {
+            return Err(TimelineError::Empty);
+        }
+        for index in 1..timeline.keyframes.len() {
+            if timeline.keyframes[index].time <= timeline.keyframes[index - 1].time {
+                return Err(TimelineError::OutOfOrder { index });
+            }
+        }
+        timeline.unwrap_yaw();
+        Ok(timeline)
+    }
+
+    pub fn to_ron(&self) -> String {
+        // one keyframe per line
+        let config = ron::ser::PrettyConfig::new().depth_limit(2);
+        ron::ser::to_string_pretty(self, config).unwrap()
+    }
+
+    // yaw wraps around at 360, which would make the camera spin all the way
+    // back round between two keyframes either side of it
+    fn unwrap_yaw(&mut self) {
+        for index in 1..self.keyframes.len() {
+            let previous = self.keyframes[index - 1].yaw;
+            let yaw = &mut self.keyframes[index].yaw;
+            *yaw = previous + (*yaw - previous + 180.0).rem_euclid(360.0) - 180.0;
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    /// The camera position and yaw and pitch in radians at `time`, held at
+    /// the first and last keyframes outside of the timeline.
+    pub fn sample(&self, time: f32) -> (Vector3<f32>, f32, f32) {
+        let keyframes = &self.keyframes;
+        if keyframes.is_empty() {
+            return ([0.0; 3], 0.0, 0.0);
+        }
+        // the keyframe the segment `time` is in starts at
+        let segment = keyframes.partition_point(|k| k.time <= time).clamp(1, keyframes.len()) - 1;
+        let t = match keyframes.get(segment + 1) {
+            Some(next) => {
+                let start = keyframes[segment];
+                let local = (time - start.time) / (next.time - start.time);
+                segment as f32 + start.easing.apply(local)
+            },
+            None => segment as f32,
+        };
+
+        let (position, angles) = match self.path {
+            Path::Linear => {
+                let a = keyframes[segment];
+                let b = keyframes.get(segment + 1).unwrap_or(&a);
+                let local = t - segment as f32;
+                let lerp = |a: f32, b: f32| a + (b - a) * local;
+                let position = std::array::from_fn(|i| lerp(a.position[i], b.position[i]));
+                (position, [lerp(a.yaw, b.yaw), lerp(a.pitch, b.pitch)])
+            },
+            Path::CatmullRom => {
+                let positions: Vec<_> = keyframes.iter().map(|k| k.position).collect();
+                let angles: Vec<_> = keyframes.iter().map(|k| [k.yaw, k.pitch, 0.0]).collect();
+                let angles = spline::sample_path(&angles, t);
+                (spline::sample_path(&positions, t), [angles[0], angles[1]])
+            },
+        };
+        (position, angles[0].to_radians(), angles[1].to_radians())
+    }
+}
+
+/// Keeps a keyframe of where the camera is every simulation step.
+pub struct Recorder {
+    keyframes: Vec<Keyframe>,
+    time: f32,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self { keyframes: Vec::new(), time: 0.0 }
+    }
+
+    // call after the camera moved in a step of `t` seconds
+    pub fn record(&mut self, camera: &Camera, t: f32) {
+        self.keyframes.push(Keyframe {
+            time: self.time,
+            position: camera.simulated_position(),
+            yaw: camera.yaw().to_degrees(),
+            pitch: camera.pitch().to_degrees(),
+            easing: Easing::Linear,
+        });
+        self.time += t;
+    }
+
+    pub fn finish(self) -> Timeline {
+        Timeline { path: Path::Linear, keyframes: self.keyframes }
+    }
+}
+
+/// Plays a timeline back. Time only moves on in simulation steps, so the
+/// camera goes through exactly the same poses on every run, whatever the
+/// frame rate, which is what makes playback usable as a benchmark.
+pub struct TimelineController {
+    timeline: Timeline,
+    time: f32,
+}
+
+impl TimelineController {
+    pub fn new(timeline: Timeline) -> Self {
+        Self { timeline, time: 0.0 }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.time >= self.timeline.duration()
+    }
+
+    fn place_camera(&self, camera: &mut Camera) {
+        let (position, yaw, pitch) = self.timeline.sample(self.time);
+        camera.move_to(position);
+        camera.set_orientation(yaw, pitch);
+    }
+}
+
+impl CameraController for TimelineController {
+    fn name(&self) -> &'static str {
+        "timeline"
+    }
+
+    // always starts from the beginning, jumping straight there
+    fn activate(&mut self, camera: &mut Camera, _context: &ControllerContext) {
+        self.time = 0.0;
+        let (position, yaw, pitch) = self.timeline.sample(0.0);
+        camera.set_position(position);
+        camera.set_orientation(yaw, pitch);
+    }
+
+    // the timeline decides where to look
+    fn look(&mut self, _camera: &mut Camera, _turn: [f32; 2]) {}
+
+    fn update(&mut self, camera: &mut Camera, _context: &ControllerContext, t: f32) {
+        self.time = (self.time + t).min(self.timeline.duration());
+        self.place_camera(camera);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(time: f32, position: Vector3<f32>, yaw: f32) -> Keyframe {
+        Keyframe { time, position, yaw, pitch: 0.0, easing: Easing::Linear }
+    }
+
+    #[test]
+    fn easing_endpoints() {
+        for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(-1.0), 0.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+            let mut last = 0.0;
+            for i in 1..=10 {
+                let eased = easing.apply(i as f32 / 10.0);
+                assert!(eased > last, "{:?} goes backwards", easing);
+                last = eased;
+            }
+        }
+    }
+
+    #[test]
+    fn passes_through_keyframes() {
+        for path in [Path::Linear, Path::CatmullRom] {
+            let timeline = Timeline {
+                path,
+                keyframes: vec![
+                    keyframe(0.0, [0.0, 80.0, 0.0], 0.0),
+                    keyframe(2.0, [10.0, 70.0, 5.0], 45.0),
+                    keyframe(3.0, [20.0, 75.0, -5.0], 90.0),
+                ],
+            };
+            for k in timeline.keyframes.iter() {
+                let (position, yaw, _) = timeline.sample(k.time);
+                assert_eq!(position, k.position);
+                assert!((yaw - k.yaw.to_radians()).abs() < 1e-5);
+            }
+            // held outside of the timeline
+            assert_eq!(timeline.sample(-1.0).0, [0.0, 80.0, 0.0]);
+            assert_eq!(timeline.sample(5.0).0, [20.0, 75.0, -5.0]);
+        }
+        let empty = Timeline { path: Path::CatmullRom, keyframes: Vec::new() };
+        assert_eq!(empty.sample(1.0), ([0.0; 3], 0.0, 0.0));
+    }
+
+    #[test]
+    fn yaw_unwrapped() {
+        let timeline = Timeline::from_ron("(path: Linear, keyframes: [
+            (time: 0.0, position: (0.0, 0.0, 0.0), yaw: 350.0, pitch: 0.0),
+            (time: 1.0, position: (0.0, 0.0, 0.0), yaw: 10.0, pitch: 0.0),
+            (time: 2.0, position: (0.0, 0.0, 0.0), yaw: -170.0, pitch: 0.0),
+        ])").unwrap();
+        let yaws: Vec<f32> = timeline.keyframes.iter().map(|k| k.yaw).collect();
+        assert_eq!(yaws, [350.0, 370.0, 190.0]);
+        // halfway it goes through 360, not back round through 180
+        assert!((timeline.sample(0.5).1 - 360f32.to_radians()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rejects_bad_timelines() {
+        assert!(matches!(Timeline::from_ron("(path: Linear, keyframes: [])"), Err(TimelineError::Empty)));
+        let out_of_order = "(path: Linear, keyframes: [
+            (time: 0.0, position: (0.0, 0.0, 0.0), yaw: 0.0, pitch: 0.0),
+            (time: 2.0, position: (0.0, 0.0, 0.0), yaw: 0.0, pitch: 0.0),
+            (time: 2.0, position: (0.0, 0.0, 0.0), yaw: 0.0, pitch: 0.0),
+        ])";
+        assert!(matches!(Timeline::from_ron(out_of_order), Err(TimelineError::OutOfOrder { index: 2 })));
+        assert!(matches!(Timeline::from_ron("(path: Bezier, keyframes: [])"), Err(TimelineError::Ron(_))));
+    }
+
+    #[test]
+    fn recording_replays() {
+        let mut camera = Camera::new(1080.0, 1920.0);
+        let mut recorder = Recorder::new();
+        let step = 1.0 / 60.0;
+        let mut poses = Vec::new();
+        for i in 0..120 {
+            let i = i as f32;
+            camera.begin_step();
+            camera.move_to([i * 0.3, 80.0 + (i * 0.1).sin(), -i * 0.2]);
+            // turns past 360 on the way
+            camera.set_orientation((300.0 + i * 2.0).to_radians(), (i * 0.5 - 30.0).to_radians());
+            recorder.record(&camera, step);
+            poses.push((camera.simulated_position(), camera.yaw(), camera.pitch()));
+        }
+
+        let timeline = Timeline::from_ron(&recorder.finish().to_ron()).unwrap();
+        let mut time = 0.0;
+        let tau = 2.0 * std::f32::consts::PI;
+        for (position, yaw, pitch) in poses {
+            let (sampled, sampled_yaw, sampled_pitch) = timeline.sample(time);
+            for i in 0..3 {
+                assert!((sampled[i] - position[i]).abs() < 1e-4, "{:?} != {:?} at {}", sampled, position, time);
+            }
+            let yaw_error = (sampled_yaw - yaw).rem_euclid(tau);
+            assert!(yaw_error.min(tau - yaw_error) < 1e-4);
+            assert!((sampled_pitch - pitch).abs() < 1e-4);
+            time += step;
+        }
+    }
+}
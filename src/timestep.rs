@@ -0,0 +1,80 @@
+/// Turns frame times into a whole number of fixed length steps, so the
+/// simulation runs at the same speed whatever the frame rate is. Time left
+/// over from a frame carries into the next one, and `alpha` says how far
+/// the frame is between the last step and the next for interpolation.
+#[derive(Clone, Copy, Debug)]
+pub struct FixedTimestep {
+    // seconds per step
+    step: f32,
+    // longest frame that is caught up on, anything longer is dropped so a
+    // stall doesn't turn into a burst of steps that takes even longer
+    max_frame: f32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    pub fn new(step: f32, max_frame: f32) -> Self {
+        Self { step, max_frame, accumulator: 0.0 }
+    }
+
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    // adds a frame's elapsed seconds and returns how many steps to run
+    pub fn advance(&mut self, elapsed: f32) -> u32 {
+        self.accumulator += elapsed.clamp(0.0, self.max_frame);
+        let steps = (self.accumulator / self.step).floor();
+        self.accumulator -= steps * self.step;
+        steps as u32
+    }
+
+    // fraction of a step since the last one ran, from 0 up to 1
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carries_over() {
+        let mut timestep = FixedTimestep::new(0.25, 1.0);
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(0.625), 2);
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(0.0625), 0);
+        assert_eq!(timestep.advance(0.0625), 1);
+    }
+
+    #[test]
+    fn long_frames_clamped() {
+        let mut timestep = FixedTimestep::new(0.25, 1.0);
+        assert_eq!(timestep.advance(10.0), 4);
+        assert_eq!(timestep.alpha(), 0.0);
+        // the rest of the long frame doesn't show up later either
+        assert_eq!(timestep.advance(0.125), 0);
+        // and time never runs backwards
+        assert_eq!(timestep.advance(-1.0), 0);
+        assert_eq!(timestep.alpha(), 0.5);
+    }
+
+    #[test]
+    fn alpha_below_one() {
+        let mut timestep = FixedTimestep::new(1.0 / 60.0, 0.25);
+        let mut total = 0;
+        for frame in 0..10000 {
+            // uneven frame times around 144hz
+            let elapsed = 1.0 / 144.0 + (frame % 7) as f32 * 0.0003;
+            total += timestep.advance(elapsed);
+            let alpha = timestep.alpha();
+            assert!((0.0..1.0).contains(&alpha), "alpha {} on frame {}", alpha, frame);
+        }
+        assert!(total > 0);
+    }
+}
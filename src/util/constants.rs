@@ -1,4 +0,0 @@
-
-pub const WIDTH: f32 = 1920.0;
-pub const HEIGHT: f32 = 1080.0;
-pub const TITLE: &'static str = "Ferrous OpenGL"; 
\ No newline at end of file
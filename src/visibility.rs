@@ -0,0 +1,293 @@
+use std::collections::{HashSet, VecDeque};
+
+use serde::Deserialize;
+
+use crate::chunkmesh::{VoxelMap, CHUNK_SIZE};
+
+// the six faces of a chunk, named the same way as the cube faces in cubemesh
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+pub enum Face {
+    East,
+    West,
+    Top,
+    Bottom,
+    North,
+    South,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [Face::East, Face::West, Face::Top, Face::Bottom, Face::North, Face::South];
+
+    // direction of the neighbouring chunk that shares this face
+    pub fn offset(self) -> [i32; 3] {
+        match self {
+            Face::East => [1, 0, 0],
+            Face::West => [-1, 0, 0],
+            Face::Top => [0, 1, 0],
+            Face::Bottom => [0, -1, 0],
+            Face::North => [0, 0, 1],
+            Face::South => [0, 0, -1],
+        }
+    }
+
+    pub fn opposite(self) -> Face {
+        match self {
+            Face::East => Face::West,
+            Face::West => Face::East,
+            Face::Top => Face::Bottom,
+            Face::Bottom => Face::Top,
+            Face::North => Face::South,
+            Face::South => Face::North,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Which pairs of a chunk's faces can see each other through non-opaque voxels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct FaceConnections(u64);
+
+impl FaceConnections {
+    pub fn connect(&mut self, a: Face, b: Face) {
+        self.0 |= 1 << (a.index() * 6 + b.index());
+        self.0 |= 1 << (b.index() * 6 + a.index());
+    }
+
+    pub fn connected(&self, a: Face, b: Face) -> bool {
+        self.0 & (1 << (a.index() * 6 + b.index())) != 0
+    }
+
+    /// Flood fills every pocket of non-opaque voxels and connects all of the
+    /// chunk faces each pocket touches.
+    pub fn compute(voxels: &VoxelMap, is_opaque: impl Fn(i8) -> bool) -> Self {
+        let mut connections = Self::default();
+        let mut visited = vec![false; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE];
+        let mut stack = Vec::new();
+        let index = |x: usize, y: usize, z: usize| (x * CHUNK_SIZE + y) * CHUNK_SIZE + z;
+        let max = CHUNK_SIZE - 1;
+
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    if visited[index(x, y, z)] || is_opaque(voxels[x][y][z]) {
+                        continue;
+                    }
+
+                    let mut touched: Vec<Face> = Vec::new();
+                    visited[index(x, y, z)] = true;
+                    stack.push([x, y, z]);
+
+                    while let Some([x, y, z]) = stack.pop() {
+                        let mut touch = |face: Face| {
+                            if !touched.contains(&face) {
+                                touched.push(face);
+                            }
+                        };
+                        if x == max { touch(Face::East); }
+                        if x == 0 { touch(Face::West); }
+                        if y == max { touch(Face::Top); }
+                        if y == 0 { touch(Face::Bottom); }
+                        if z == max { touch(Face::North); }
+                        if z == 0 { touch(Face::South); }
+
+                        for face in Face::ALL {
+                            let [dx, dy, dz] = face.offset();
+                            let (nx, ny, nz) = (x as i32 + dx, y as i32 + dy, z as i32 + dz);
+                            if nx < 0 || ny < 0 || nz < 0 || nx > max as i32 || ny > max as i32 || nz > max as i32 {
+                                continue;
+                            }
+                            let (nx, ny, nz) = (nx as usize, ny as usize, nz as usize);
+                            if visited[index(nx, ny, nz)] || is_opaque(voxels[nx][ny][nz]) {
+                                continue;
+                            }
+                            visited[index(nx, ny, nz)] = true;
+                            stack.push([nx, ny, nz]);
+                        }
+                    }
+
+                    for &a in &touched {
+                        for &b in &touched {
+                            connections.connect(a, b);
+                        }
+                    }
+                }
+            }
+        }
+        connections
+    }
+}
+
+/// Breadth first search outward from the camera's chunk. A chunk is only
+/// entered if the face we leave through is connected to the face we came in
+/// from, and we never step back towards the camera.
+/// `connections` returns `None` for chunks outside of the loaded world.
+pub fn visible_chunks(
+    start: [i32; 3],
+    connections: impl Fn([i32; 3]) -> Option<FaceConnections>,
+) -> Vec<[i32; 3]> {
+    let mut visible = Vec::new();
+    let mut visited = HashSet::new();
+    // (chunk, face it was entered through, bitmask of directions travelled)
+    let mut queue: VecDeque<([i32; 3], Option<Face>, u8)> = VecDeque::new();
+
+    if connections(start).is_none() {
+        // the camera is outside of the world, let the caller decide what to draw
+        return visible;
+    }
+
+    visited.insert(start);
+    queue.push_back((start, None, 0));
+
+    while let Some((chunk, entered, travelled)) = queue.pop_front() {
+        visible.push(chunk);
+        let chunk_connections = match connections(chunk) {
+            Some(c) => c,
+            None => continue,
+        };
+
+        for out in Face::ALL {
+            if travelled & (1 << out.opposite().index()) != 0 {
+                continue;
+            }
+            if let Some(entered) = entered {
+                if !chunk_connections.connected(entered, out) {
+                    continue;
+                }
+            }
+
+            let offset = out.offset();
+            let next = [chunk[0] + offset[0], chunk[1] + offset[1], chunk[2] + offset[2]];
+            if visited.contains(&next) || connections(next).is_none() {
+                continue;
+            }
+            visited.insert(next);
+            queue.push_back((next, Some(out.opposite()), travelled | (1 << out.index())));
+        }
+    }
+
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const AIR: i8 = 0;
+    const STONE: i8 = 1;
+
+    fn filled(block: i8) -> Box<VoxelMap> {
+        Box::new([[[block; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE])
+    }
+
+    fn compute(voxels: &VoxelMap) -> FaceConnections {
+        FaceConnections::compute(voxels, |block| block != AIR)
+    }
+
+    // every pair of different faces that is connected
+    fn pairs(connections: FaceConnections) -> Vec<(Face, Face)> {
+        let mut pairs = Vec::new();
+        for (i, &a) in Face::ALL.iter().enumerate() {
+            for &b in &Face::ALL[i + 1..] {
+                if connections.connected(a, b) {
+                    pairs.push((a, b));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn solid_chunk() {
+        assert_eq!(compute(&filled(STONE)), FaceConnections::default());
+    }
+
+    #[test]
+    fn empty_chunk() {
+        let connections = compute(&filled(AIR));
+        for a in Face::ALL {
+            for b in Face::ALL {
+                assert!(connections.connected(a, b), "{:?} and {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn tunnel() {
+        let mut voxels = filled(STONE);
+        for x in 0..CHUNK_SIZE {
+            voxels[x][5][7] = AIR;
+        }
+        assert_eq!(pairs(compute(&voxels)), [(Face::East, Face::West)]);
+
+        // a cave that doesn't reach the edges connects nothing
+        let mut voxels = filled(STONE);
+        for x in 4..20 {
+            voxels[x][5][7] = AIR;
+            voxels[10][x][7] = AIR;
+        }
+        assert_eq!(compute(&voxels), FaceConnections::default());
+    }
+
+    #[test]
+    fn bent_tunnel() {
+        // in from the bottom, out through the north face
+        let mut voxels = filled(STONE);
+        for y in 0..10 {
+            voxels[3][y][3] = AIR;
+        }
+        for z in 3..CHUNK_SIZE {
+            voxels[3][9][z] = AIR;
+        }
+        assert_eq!(pairs(compute(&voxels)), [(Face::Bottom, Face::North)]);
+    }
+
+    // a small world of chunks in the x-y plane, `layout` rows going from
+    // the top down with `#` for solid chunks and `.` for empty ones
+    fn world(layout: &[&str]) -> HashMap<[i32; 3], FaceConnections> {
+        let solid = compute(&filled(STONE));
+        let empty = compute(&filled(AIR));
+        let mut chunks = HashMap::new();
+        for (row, line) in layout.iter().enumerate() {
+            let y = (layout.len() - 1 - row) as i32;
+            for (x, c) in line.chars().enumerate() {
+                chunks.insert([x as i32, y, 0], if c == '#' { solid } else { empty });
+            }
+        }
+        chunks
+    }
+
+    #[test]
+    fn sealed_chunk() {
+        let chunks = world(&["...#..."]);
+        let mut visible = visible_chunks([1, 0, 0], |chunk| chunks.get(&chunk).copied());
+        visible.sort();
+        // the wall itself is seen, nothing behind it
+        assert_eq!(visible, [[0, 0, 0], [1, 0, 0], [2, 0, 0], [3, 0, 0]]);
+    }
+
+    #[test]
+    fn never_turns_back() {
+        // the chunk above the wall can only be reached by going right, up
+        // and then left again, back towards the camera
+        let chunks = world(&[
+            "..",
+            "#.",
+            "..",
+        ]);
+        let visible = visible_chunks([0, 0, 0], |chunk| chunks.get(&chunk).copied());
+        assert!(visible.contains(&[1, 2, 0]));
+        assert!(visible.contains(&[0, 1, 0]));
+        assert!(!visible.contains(&[0, 2, 0]));
+        assert_eq!(visible.len(), 5);
+    }
+
+    #[test]
+    fn outside_of_the_world() {
+        let chunks = world(&["..."]);
+        assert!(visible_chunks([10, 0, 0], |chunk| chunks.get(&chunk).copied()).is_empty());
+    }
+}
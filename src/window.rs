@@ -0,0 +1,93 @@
+use glium::glutin::{
+    dpi::PhysicalSize,
+    event_loop::EventLoop,
+    monitor::{MonitorHandle, VideoMode},
+    window::{Fullscreen, WindowBuilder},
+    ContextBuilder,
+};
+use glium::Display;
+use serde::{Deserialize, Serialize};
+
+pub const TITLE: &str = "Ferrous OpenGL";
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum FullscreenMode {
+    // a window covering the whole monitor, quick to switch to and from
+    Borderless,
+    // takes over the monitor at its best video mode
+    Exclusive,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    // size of the window when it isn't fullscreen, in physical pixels
+    pub width: u32,
+    pub height: u32,
+    // whether to start fullscreen, and which kind ToggleFullscreen switches to
+    pub fullscreen: bool,
+    pub fullscreen_mode: FullscreenMode,
+    // vsync can only be picked when the window is created
+    pub vsync: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            fullscreen: false,
+            fullscreen_mode: FullscreenMode::Borderless,
+            vsync: true,
+        }
+    }
+}
+
+pub fn create(settings: &WindowSettings) -> (Display, EventLoop<()>) {
+    let events_loop = EventLoop::new();
+    let wb = WindowBuilder::new()
+        .with_inner_size(PhysicalSize::new(settings.width, settings.height))
+        .with_title(TITLE);
+    // the world is drawn into a scene::SceneTarget with its own depth
+    // buffer, so the window doesn't need one
+    let cb = ContextBuilder::new()
+        .with_vsync(settings.vsync);
+    let display = Display::new(wb, cb, &events_loop).unwrap();
+    if settings.fullscreen {
+        set_fullscreen(&display, Some(settings.fullscreen_mode));
+    }
+    (display, events_loop)
+}
+
+// the biggest and then fastest video mode the monitor has
+fn best_video_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    monitor.video_modes().max_by_key(|mode| {
+        let size = mode.size();
+        (size.width * size.height, mode.refresh_rate())
+    })
+}
+
+/// Makes the window fullscreen on the monitor it's on, or puts it back in a
+/// window with `None`.
+pub fn set_fullscreen(display: &Display, mode: Option<FullscreenMode>) {
+    let gl_window = display.gl_window();
+    let window = gl_window.window();
+    let monitor = window.current_monitor();
+    let fullscreen = match mode {
+        None => None,
+        Some(FullscreenMode::Borderless) => Some(Fullscreen::Borderless(monitor)),
+        // some platforms list no video modes, borderless is the next best thing
+        Some(FullscreenMode::Exclusive) => match monitor.as_ref().and_then(best_video_mode) {
+            Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+            None => {
+                eprintln!("no video modes for exclusive fullscreen, going borderless");
+                Some(Fullscreen::Borderless(monitor))
+            },
+        },
+    };
+    window.set_fullscreen(fullscreen);
+}
+
+pub fn is_fullscreen(display: &Display) -> bool {
+    display.gl_window().window().fullscreen().is_some()
+}
@@ -0,0 +1,332 @@
+extern crate noise;
+
+use std::collections::{HashMap, HashSet};
+
+use noise::{NoiseFn, Perlin};
+
+use crate::block::{self, BlockRegistry};
+use crate::lod;
+use crate::chunkmesh::{ChunkMesh, VoxelMap, CHUNK_SIZE};
+use crate::tick::{self, Random, TickScheduler, VoxelGrid, RANDOM_TICKS_PER_CHUNK};
+use crate::visibility::{self, Face, FaceConnections};
+
+// number of chunks in each direction from the origin on the x and z axes
+pub const WORLD_RADIUS: i32 = 4;
+// number of chunks stacked on the y axis
+pub const WORLD_HEIGHT: i32 = 3;
+// air at or below this height above the terrain is filled with water
+pub const SEA_LEVEL: f64 = 50.0;
+// caves at or below this height are flooded with lava
+pub const LAVA_LEVEL: f64 = 10.0;
+// terrain surfacing this close above sea level or lower is covered in sand
+pub const BEACH_HEIGHT: f64 = 2.0;
+
+pub struct Chunk {
+    pub mesh: ChunkMesh,
+    // downsampled meshes for LOD levels 1 and up
+    pub lods: Vec<ChunkMesh>,
+    pub connections: FaceConnections,
+}
+
+impl Chunk {
+    fn new(voxels: VoxelMap, blocks: &BlockRegistry) -> Self {
+        let mut mesh = ChunkMesh::from_voxels(voxels);
+        mesh.generate_mesh(blocks);
+        let lods = lod::generate_lod_meshes(mesh.voxels(), blocks);
+        let connections = FaceConnections::compute(mesh.voxels(), |b| blocks.is_opaque(b));
+        Self { mesh, lods, connections }
+    }
+
+    // the mesh for a detail level, where level 0 is full resolution
+    pub fn lod_mesh(&self, level: usize) -> &ChunkMesh {
+        match level {
+            0 => &self.mesh,
+            _ => &self.lods[level - 1],
+        }
+    }
+}
+
+pub struct World {
+    pub chunks: HashMap<[i32; 3], Chunk>,
+    scheduler: TickScheduler,
+    // picks the blocks that get random ticks
+    random: Random,
+    // chunks whose voxels changed since they were last meshed
+    dirty: HashSet<[i32; 3]>,
+}
+
+impl World {
+    pub fn generate(seed: u32, blocks: &BlockRegistry) -> Self {
+        let palette = Palette::new(blocks);
+        let height_noise = Perlin::new(seed);
+        let cave_noise = Perlin::new(seed.wrapping_add(1));
+        let mut voxels = Vec::new();
+
+        for cx in -WORLD_RADIUS..WORLD_RADIUS {
+            for cy in 0..WORLD_HEIGHT {
+                for cz in -WORLD_RADIUS..WORLD_RADIUS {
+                    voxels.push(([cx, cy, cz], generate_voxels([cx, cy, cz], &palette, &height_noise, &cave_noise)));
+                }
+            }
+        }
+        Self::from_voxels(voxels, seed, blocks)
+    }
+
+    /// A world made of the given chunks, like ones loaded from a save.
+    pub fn from_voxels(voxels: Vec<([i32; 3], VoxelMap)>, seed: u32, blocks: &BlockRegistry) -> Self {
+        let chunks = voxels
+            .into_iter()
+            .map(|(position, voxels)| (position, Chunk::new(voxels, blocks)))
+            .collect();
+        let mut world = Self {
+            chunks,
+            scheduler: TickScheduler::new(),
+            random: Random::new(seed as u64),
+            dirty: HashSet::new(),
+        };
+        world.schedule_fluids(blocks);
+        world
+    }
+
+    // start fluids that generation left next to empty space flowing
+    fn schedule_fluids(&mut self, blocks: &BlockRegistry) {
+        let size = CHUNK_SIZE as i32;
+        let mut chunks: Vec<[i32; 3]> = self.chunks.keys().copied().collect();
+        chunks.sort();
+        for chunk in chunks {
+            let voxels = self.chunks[&chunk].mesh.voxels();
+            let mut flowing = Vec::new();
+            for (x, plane) in voxels.iter().enumerate() {
+                for (y, column) in plane.iter().enumerate() {
+                    for (z, &voxel) in column.iter().enumerate() {
+                        let fluid = match blocks.fluid(voxel) {
+                            Some(fluid) => fluid,
+                            None => continue,
+                        };
+                        let position = [chunk[0] * size + x as i32, chunk[1] * size + y as i32, chunk[2] * size + z as i32];
+                        // air above is just the surface, fluid only moves into air beside or below it
+                        let open = Face::ALL.iter().filter(|&&f| f != Face::Top).any(|&face| {
+                            let [dx, dy, dz] = face.offset();
+                            self.get([position[0] + dx, position[1] + dy, position[2] + dz]) == Some(block::AIR)
+                        });
+                        if open {
+                            flowing.push((position, fluid.definition.tick_interval));
+                        }
+                    }
+                }
+            }
+            for (position, delay) in flowing {
+                self.scheduler.schedule(position, delay);
+            }
+        }
+    }
+
+    /// Changes a block and schedules updates for it and its neighbours,
+    /// at the pace of whichever of the new and old blocks reacts to them.
+    /// Its chunk is meshed again on the next `tick`.
+    pub fn set_block(&mut self, position: [i32; 3], block: i8, blocks: &BlockRegistry) {
+        let size = CHUNK_SIZE as i32;
+        let chunk = [position[0].div_euclid(size), position[1].div_euclid(size), position[2].div_euclid(size)];
+        let old = match self.get(position) {
+            Some(old) => old,
+            None => return,
+        };
+        let [x, y, z] = [position[0].rem_euclid(size), position[1].rem_euclid(size), position[2].rem_euclid(size)];
+        self.chunks.get_mut(&chunk).unwrap().mesh.set_voxel(x as usize, y as usize, z as usize, block);
+        self.dirty.insert(chunk);
+
+        let delay = blocks.tick_delay(block).or_else(|| blocks.tick_delay(old)).unwrap_or(1);
+        self.scheduler.schedule(position, delay);
+    }
+
+    /// Advances the world by one game tick, running the updates due on it
+    /// and a few random ticks in every chunk, then meshes any chunks that
+    /// changed and returns them so their buffers can be uploaded again.
+    pub fn tick(&mut self, blocks: &BlockRegistry) -> Vec<[i32; 3]> {
+        let changes: Vec<([i32; 3], i8)> = self
+            .scheduler
+            .advance()
+            .into_iter()
+            .filter_map(|position| Some((position, tick::scheduled_update(self, blocks, position)?)))
+            .collect();
+        for (position, block) in changes {
+            self.set_block(position, block, blocks);
+        }
+
+        let size = CHUNK_SIZE as u32;
+        let mut chunks: Vec<[i32; 3]> = self.chunks.keys().copied().collect();
+        chunks.sort();
+        for chunk in chunks {
+            for _ in 0..RANDOM_TICKS_PER_CHUNK {
+                let mut random = self.random;
+                let position = [
+                    chunk[0] * size as i32 + random.below(size) as i32,
+                    chunk[1] * size as i32 + random.below(size) as i32,
+                    chunk[2] * size as i32 + random.below(size) as i32,
+                ];
+                let change = tick::random_update(self, blocks, position, &mut random);
+                self.random = random;
+                if let Some((position, block)) = change {
+                    self.set_block(position, block, blocks);
+                }
+            }
+        }
+
+        let mut changed: Vec<[i32; 3]> = self.dirty.drain().collect();
+        changed.sort();
+        for chunk in changed.iter() {
+            let c = self.chunks.get_mut(chunk).unwrap();
+            *c = Chunk::new(*c.mesh.voxels(), blocks);
+        }
+        changed
+    }
+
+    /// Steps through the blocks along a ray, returning the first block that
+    /// isn't air or a fluid along with the cell the ray was in just before
+    /// it, where a block placed against it would go.
+    pub fn raycast(
+        &self,
+        blocks: &BlockRegistry,
+        origin: [f32; 3],
+        direction: [f32; 3],
+        distance: f32,
+    ) -> Option<([i32; 3], [i32; 3])> {
+        let mut cell = [origin[0].floor() as i32, origin[1].floor() as i32, origin[2].floor() as i32];
+        let mut step = [0; 3];
+        // distance along the ray to the next cell boundary on each axis, and
+        // between boundaries
+        let mut next = [f32::INFINITY; 3];
+        let mut delta = [f32::INFINITY; 3];
+        for i in 0..3 {
+            if direction[i] > 0.0 {
+                step[i] = 1;
+                delta[i] = 1.0 / direction[i];
+                next[i] = (cell[i] as f32 + 1.0 - origin[i]) * delta[i];
+            } else if direction[i] < 0.0 {
+                step[i] = -1;
+                delta[i] = -1.0 / direction[i];
+                next[i] = (origin[i] - cell[i] as f32) * delta[i];
+            }
+        }
+
+        loop {
+            let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+            if next[axis] > distance {
+                return None;
+            }
+            let previous = cell;
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+            // outside of the world counts as air so it can be looked into from above
+            let block = self.get(cell).unwrap_or(block::AIR);
+            if block != block::AIR && blocks.fluid(block).is_none() {
+                return Some((cell, previous));
+            }
+        }
+    }
+
+    // the chunk that contains a point in world space
+    pub fn chunk_at(position: [f32; 3]) -> [i32; 3] {
+        let size = CHUNK_SIZE as f32;
+        [
+            (position[0] / size).floor() as i32,
+            (position[1] / size).floor() as i32,
+            (position[2] / size).floor() as i32,
+        ]
+    }
+
+    /// Chunks that could be seen from `position`, falling back to every
+    /// chunk when the camera is outside of the generated world.
+    pub fn visible_chunks(&self, position: [f32; 3]) -> Vec<[i32; 3]> {
+        let visible = visibility::visible_chunks(Self::chunk_at(position), |chunk| {
+            self.chunks.get(&chunk).map(|c| c.connections)
+        });
+        if visible.is_empty() {
+            return self.chunks.keys().copied().collect();
+        }
+        visible
+    }
+}
+
+impl VoxelGrid for World {
+    fn get(&self, position: [i32; 3]) -> Option<i8> {
+        let size = CHUNK_SIZE as i32;
+        let chunk = [position[0].div_euclid(size), position[1].div_euclid(size), position[2].div_euclid(size)];
+        let [x, y, z] = [position[0].rem_euclid(size), position[1].rem_euclid(size), position[2].rem_euclid(size)];
+        self.chunks.get(&chunk).map(|c| c.mesh.voxels()[x as usize][y as usize][z as usize])
+    }
+}
+
+// ids of the blocks world generation places
+struct Palette {
+    grass: i8,
+    dirt: i8,
+    stone: i8,
+    water: i8,
+    lava: i8,
+    sand: i8,
+    flower: i8,
+}
+
+impl Palette {
+    fn new(blocks: &BlockRegistry) -> Self {
+        let id = |name: &str| {
+            blocks.id(name).unwrap_or_else(|| panic!("world generation needs a \"{}\" block definition", name))
+        };
+        Self {
+            grass: id("grass"),
+            dirt: id("dirt"),
+            stone: id("stone"),
+            water: id("water"),
+            lava: id("lava"),
+            sand: id("sand"),
+            flower: id("flower"),
+        }
+    }
+}
+
+// noise heightmap with caves carved out by 3d noise
+fn generate_voxels(chunk: [i32; 3], palette: &Palette, height_noise: &Perlin, cave_noise: &Perlin) -> VoxelMap {
+    let mut voxels: VoxelMap = [[[block::AIR; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+    let size = CHUNK_SIZE as i32;
+
+    for (x, plane) in voxels.iter_mut().enumerate() {
+        for z in 0..CHUNK_SIZE {
+            let wx = (chunk[0] * size + x as i32) as f64;
+            let wz = (chunk[2] * size + z as i32) as f64;
+            let height = height_noise.get([wx / 64.0, wz / 64.0]) * 16.0
+                + height_noise.get([wx / 16.0, wz / 16.0]) * 4.0
+                + 56.0;
+
+            for (y, column) in plane.iter_mut().enumerate() {
+                let wy = (chunk[1] * size + y as i32) as f64;
+                if wy > height {
+                    if wy <= SEA_LEVEL {
+                        column[z] = palette.water;
+                    } else if wy - 1.0 <= height && height_noise.get([wx * 0.37, wz * 0.37]) > 0.5 {
+                        column[z] = palette.flower;
+                    }
+                    continue;
+                }
+                let cave = cave_noise.get([wx / 24.0, wy / 16.0, wz / 24.0]);
+                // keep a solid floor at the bottom of the world
+                if wy > 0.0 && cave > 0.45 {
+                    if wy <= LAVA_LEVEL {
+                        column[z] = palette.lava;
+                    }
+                    continue;
+                }
+                column[z] = if wy > height - 3.0 && height <= SEA_LEVEL + BEACH_HEIGHT {
+                    palette.sand
+                } else if wy > height - 1.0 {
+                    palette.grass
+                } else if wy > height - 4.0 {
+                    palette.dirt
+                } else {
+                    palette.stone
+                };
+            }
+        }
+    }
+    voxels
+}